@@ -0,0 +1,118 @@
+use super::Hai;
+
+/// Chance of a hai being machihai of ryanmen, judged by visible hai.
+///
+/// # Member
+/// * NoChance: every ryanmen waiting for the hai needs a hai all 4 of which are visible.
+/// * OneChance: some ryanmen waiting for the hai is possible only with the last one of a hai.
+/// * Normal: otherwise.
+///
+/// # Japanese
+/// * NoChance: ノーチャンス
+/// * OneChance: ワンチャンス
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ChanceLevel {
+    NoChance,
+    OneChance,
+    Normal,
+}
+
+/// Return the chance level of hai, with the number of each type of visible hai
+/// indexed like `Hai::to_index()`.
+/// Return None for jihai, which can never be waited by ryanmen.
+/// Tanki, shanpon and kanchan are not considered.
+///
+/// # Japanese
+/// * tanki: 単騎
+/// * shanpon: 双碰
+/// * kanchan: 嵌張
+pub fn chance_level(hai: &Hai, visible: &[u8; 34]) -> Option<ChanceLevel> {
+    let number = hai.number()?;
+    let index = hai.to_index();
+    let rest = |offset: i8| 4u8.saturating_sub(visible[(index as i8 + offset) as usize]);
+
+    // Ryanmen is made of 2 to 8, and limited by the hai with fewer left.
+    let mut ryanmen_rest = vec![];
+    if number <= 6 {
+        ryanmen_rest.push(rest(1).min(rest(2)));
+    }
+    if number >= 4 {
+        ryanmen_rest.push(rest(-1).min(rest(-2)));
+    }
+    match ryanmen_rest.into_iter().max() {
+        None | Some(0) => Some(ChanceLevel::NoChance),
+        Some(1) => Some(ChanceLevel::OneChance),
+        Some(_) => Some(ChanceLevel::Normal),
+    }
+}
+
+/// Safety of discarding a hai against an opponent in riichi.
+///
+/// # Member
+/// * Safe: the hai cannot deal in to the opponent.
+/// * Likely: the hai cannot deal in to ryanmen, but may deal in to other machi.
+/// * Dangerous: otherwise.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Safety {
+    Dangerous,
+    Likely,
+    Safe,
+}
+
+/// Reason of a hai being safe.
+///
+/// # Member
+/// * Genbutsu: the hai is in sutehai of the opponent, who is furiten on it.
+/// * Suji: every ryanmen waiting for the hai is furiten by sutehai of the opponent.
+/// * NoChance: `chance_level()` of the hai is NoChance.
+///
+/// # Japanese
+/// * Genbutsu: 現物
+/// * Suji: 筋
+/// * furiten: 振聴
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SafetyReason {
+    Genbutsu,
+    Suji,
+    NoChance,
+}
+
+/// Return the safety of discarding hai against an opponent in riichi, with all reasons
+/// found, given sutehai of the opponent and the number of each type of visible hai
+/// indexed like `Hai::to_index()`.
+/// Jihai which is not genbutsu is Dangerous, as it may be waited by tanki or shanpon.
+pub fn is_safe(hai: &Hai, sutehai: &[Hai], visible: &[u8; 34]) -> (Safety, Vec<SafetyReason>) {
+    let mut reasons = vec![];
+    if sutehai.contains(hai) {
+        reasons.push(SafetyReason::Genbutsu);
+    }
+    if let Some(number) = hai.number() {
+        let index = hai.to_index();
+        // Suji of n are n - 3 and n + 3 of the same suit, if they exist.
+        let mut suji = vec![];
+        if number >= 4 {
+            suji.push(Hai::from_index(index - 3));
+        }
+        if number <= 6 {
+            suji.push(Hai::from_index(index + 3));
+        }
+        if suji
+            .iter()
+            .all(|suji| suji.is_some_and(|suji| sutehai.contains(&suji)))
+        {
+            reasons.push(SafetyReason::Suji);
+        }
+        if chance_level(hai, visible) == Some(ChanceLevel::NoChance) {
+            reasons.push(SafetyReason::NoChance);
+        }
+    }
+
+    let safety = if reasons.contains(&SafetyReason::Genbutsu) {
+        Safety::Safe
+    } else if reasons.is_empty() {
+        Safety::Dangerous
+    } else {
+        Safety::Likely
+    };
+    (safety, reasons)
+}
@@ -1,17 +1,24 @@
-use super::{Hai, Haiyama, MachiCondition, Mentsu, PlayerNumber, Tehai};
+use super::{Hai, Haiyama, MachiCondition, Mentsu, PlayerNumber, Suit, Tehai};
 use serde_json::json;
 use std::collections::BTreeSet;
 
+/// Version of the json format of `GameManager::to_json()`.
+/// Increase it when the format changes incompatibly.
+pub const JSON_VERSION: u64 = 1;
+
 /// The game manager.
 /// Include everything that a complete mahjong game need.
 #[derive(Clone, Debug)]
 pub struct GameManager {
     haiyama: Haiyama,
     tehai: Option<Tehai>,
-    sutehai_type: BTreeSet<Hai>,
+    sutehai: Vec<Hai>,
     pub state: State,
     player_number: PlayerNumber,
-    history: Vec<(Operation, State, BTreeSet<Hai>)>,
+    history: Vec<(Operation, State, Vec<Hai>)>,
+    riichi: Option<usize>,
+    riichi_ankan: bool,
+    live_wall_remaining: Option<usize>,
 }
 
 /// Type of kan.
@@ -65,12 +72,23 @@ pub enum HaiyamaOperation {
 }
 
 /// Operation on tehai.
+///
+/// `tsumogiri` of `Discard` and `Riichi` is decided by game manager when operating,
+/// the input value is ignored. It is true if the discarded hai is the one just added.
+///
+/// `Riichi` discards the hai and declares riichi, only legal for a closed tehai
+/// which is tenpai after discarding.
+///
+/// # Japanese
+/// * tsumogiri: ツモ切り
+/// * riichi: 立直
 #[derive(Clone, Debug)]
 pub enum TehaiOperation {
     Initialize(Tehai),
     Add { hai: Hai, haiyama_sensitive: bool },
-    Discard(Hai),
+    Discard { hai: Hai, tsumogiri: bool },
     Naku { kind: Naku, haiyama_sensitive: bool },
+    Riichi { hai: Hai, tsumogiri: bool },
 }
 
 /// Valid operation for game manager.
@@ -83,8 +101,28 @@ pub enum Operation {
     Tehai(TehaiOperation),
 }
 
+/// Kind of operation, without hai of it.
+///
+/// Every kind has a stable opcode, which is never changed or reused.
+/// `Kan::Unknown` is only used for input, game manager records the resolved kind of kan.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum OperationKind {
+    HaiyamaAdd = 0,
+    HaiyamaDiscard = 1,
+    TehaiInitialize = 2,
+    TehaiAdd = 3,
+    TehaiDiscard = 4,
+    Chii = 5,
+    Pon = 6,
+    Daiminkan = 7,
+    Kakan = 8,
+    Ankan = 9,
+    UnknownKan = 10,
+    Riichi = 11,
+}
+
 /// Game state.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum State {
     WaitToInit,
     FullHai,
@@ -92,6 +130,60 @@ pub enum State {
     WaitForRinshanhai,
 }
 
+/// Kind of furiten.
+///
+/// # Japanese
+/// * furiten: 振り聴
+///
+/// # Member
+/// * Permanent: Some machihai is in sutehai of self.
+/// * Temporary: Some machihai is discarded by others after last sutehai of self.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FuritenKind {
+    None,
+    Temporary,
+    Permanent,
+}
+
+/// Number of sutehai counted as early in `DiscardProfile`.
+pub const EARLY_DISCARD_NUMBER: usize = 6;
+
+/// Summary of own sutehai for review.
+///
+/// # Japanese
+/// * tsumogiri: ツモ切り
+///
+/// # Member
+/// * suit: number of sutehai of each suit -- manzu, pinzu, souzu and jihai.
+/// * early_jihai: number of jihai in the first `EARLY_DISCARD_NUMBER` sutehai.
+/// * tsumogiri: number of sutehai discarded just after added.
+/// * riichi: index of the sutehai declaring riichi, or None if riichi is not declared.
+///
+/// Sutehai declaring riichi is counted like other sutehai.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DiscardProfile {
+    pub suit: [usize; 4],
+    pub early_jihai: usize,
+    pub tsumogiri: usize,
+    pub riichi: Option<usize>,
+}
+
+impl DiscardProfile {
+    /// Return the suit most discarded except jihai, or None if no sutehai is a number hai.
+    /// The first suit is taken for ties.
+    pub fn dominant_suit(&self) -> Option<Suit> {
+        let suit_vec = [Suit::Manzu, Suit::Pinzu, Suit::Souzu];
+        let max = *self.suit[..3].iter().max().unwrap();
+        if max == 0 {
+            return None;
+        }
+        suit_vec
+            .iter()
+            .copied()
+            .find(|suit| self.suit[*suit as usize] == max)
+    }
+}
+
 impl Kan {
     pub fn to_json(&self) -> serde_json::Value {
         let (tp, kantsu, rinshanhai) = match self {
@@ -150,9 +242,10 @@ impl TehaiOperation {
                 "hai": hai.to_string(),
                 "haiyama_sensitive": haiyama_sensitive,
             }),
-            TehaiOperation::Discard(hai) => json!({
+            TehaiOperation::Discard { hai, tsumogiri } => json!({
                 "operation": "discard",
                 "hai": hai.to_string(),
+                "tsumogiri": tsumogiri,
             }),
             TehaiOperation::Naku {
                 kind,
@@ -162,6 +255,11 @@ impl TehaiOperation {
                 "naku": kind.to_json(),
                 "haiyama_sensitive": haiyama_sensitive,
             }),
+            TehaiOperation::Riichi { hai, tsumogiri } => json!({
+                "operation": "riichi",
+                "hai": hai.to_string(),
+                "tsumogiri": tsumogiri,
+            }),
         }
     }
 }
@@ -194,6 +292,37 @@ impl HaiyamaOperation {
 }
 
 impl Operation {
+    /// Return the kind of operation.
+    pub fn kind(&self) -> OperationKind {
+        match self {
+            Operation::Haiyama {
+                kind: HaiyamaOperation::Add(_),
+                ..
+            } => OperationKind::HaiyamaAdd,
+            Operation::Haiyama {
+                kind: HaiyamaOperation::Discard(_),
+                ..
+            } => OperationKind::HaiyamaDiscard,
+            Operation::Tehai(TehaiOperation::Initialize(_)) => OperationKind::TehaiInitialize,
+            Operation::Tehai(TehaiOperation::Add { .. }) => OperationKind::TehaiAdd,
+            Operation::Tehai(TehaiOperation::Discard { .. }) => OperationKind::TehaiDiscard,
+            Operation::Tehai(TehaiOperation::Naku { kind, .. }) => match kind {
+                Naku::Chii { .. } => OperationKind::Chii,
+                Naku::Pon(_) => OperationKind::Pon,
+                Naku::Kan(Kan::Daiminkan { .. }) => OperationKind::Daiminkan,
+                Naku::Kan(Kan::Kakan { .. }) => OperationKind::Kakan,
+                Naku::Kan(Kan::Ankan { .. }) => OperationKind::Ankan,
+                Naku::Kan(Kan::Unknown { .. }) => OperationKind::UnknownKan,
+            },
+            Operation::Tehai(TehaiOperation::Riichi { .. }) => OperationKind::Riichi,
+        }
+    }
+
+    /// Return the opcode of the kind of operation. See `OperationKind`.
+    pub fn opcode(&self) -> u8 {
+        self.kind().opcode()
+    }
+
     pub fn to_json(&self) -> serde_json::Value {
         match self {
             Operation::Tehai(tehai_operation) => json!({
@@ -212,16 +341,105 @@ impl Operation {
     }
 }
 
+impl OperationKind {
+    /// Return the opcode of kind.
+    pub fn opcode(&self) -> u8 {
+        *self as u8
+    }
+
+    /// Return the kind of opcode, or None if unknown.
+    pub fn from_opcode(opcode: u8) -> Option<Self> {
+        match opcode {
+            0 => Some(OperationKind::HaiyamaAdd),
+            1 => Some(OperationKind::HaiyamaDiscard),
+            2 => Some(OperationKind::TehaiInitialize),
+            3 => Some(OperationKind::TehaiAdd),
+            4 => Some(OperationKind::TehaiDiscard),
+            5 => Some(OperationKind::Chii),
+            6 => Some(OperationKind::Pon),
+            7 => Some(OperationKind::Daiminkan),
+            8 => Some(OperationKind::Kakan),
+            9 => Some(OperationKind::Ankan),
+            10 => Some(OperationKind::UnknownKan),
+            11 => Some(OperationKind::Riichi),
+            _ => None,
+        }
+    }
+}
+
 impl GameManager {
     /// Create a instance of GameManager.
     pub fn new(player_number: PlayerNumber) -> Self {
         Self {
             haiyama: Haiyama::new(player_number),
             tehai: None,
-            sutehai_type: BTreeSet::new(),
+            sutehai: vec![],
             state: State::WaitToInit,
             player_number,
             history: vec![],
+            riichi: None,
+            riichi_ankan: true,
+            live_wall_remaining: None,
+        }
+    }
+
+    /// Create a game manager in the middle of a game, with empty history, so operations
+    /// before it cannot be undone. State must be FullHai or LackOneHai, matching the
+    /// number of hai on hand. Haiyama must not include hai on hand or sutehai, so there
+    /// are at most 4 of each type of hai among them.
+    pub fn resume(
+        player_number: PlayerNumber,
+        tehai: Tehai,
+        haiyama: Haiyama,
+        state: State,
+        sutehai: Vec<Hai>,
+    ) -> Result<Self, String> {
+        let mut hai_vec = tehai.juntehai.clone();
+        for mentsu in &tehai.fuuro {
+            match mentsu {
+                Mentsu::Juntsu(a, b, c) => hai_vec.extend([*a, *b, *c].iter()),
+                Mentsu::Koutsu(hai) => hai_vec.extend([*hai; 3].iter()),
+                Mentsu::Kantsu(hai) | Mentsu::Ankantsu(hai) => hai_vec.extend([*hai; 4].iter()),
+            }
+        }
+        hai_vec.extend(sutehai.iter());
+        if !Hai::check_iter_valid(hai_vec.iter(), player_number) {
+            return Err(format!("Invalid hai for {:?} players.", player_number));
+        }
+        for hai in Hai::all_type(player_number) {
+            let in_haiyama = if haiyama.can_draw(&hai) {
+                haiyama[&hai]
+            } else {
+                0
+            };
+            let number = hai_vec.iter().filter(|item| **item == hai).count() + in_haiyama as usize;
+            if number > 4 {
+                return Err(format!(
+                    "{} of {} found in tehai, sutehai and haiyama.",
+                    number, hai
+                ));
+            }
+        }
+        let game_manager = Self {
+            haiyama,
+            tehai: Some(tehai),
+            sutehai,
+            state,
+            player_number,
+            history: vec![],
+            riichi: None,
+            riichi_ankan: true,
+            live_wall_remaining: None,
+        };
+        match state {
+            State::FullHai | State::LackOneHai if game_manager.check_tile_total() => {
+                Ok(game_manager)
+            }
+            State::FullHai | State::LackOneHai => Err(format!(
+                "The number of hai on hand does not match {:?}.",
+                state
+            )),
+            _ => Err(format!("Cannot resume to {:?}.", state)),
         }
     }
 
@@ -230,49 +448,265 @@ impl GameManager {
         self
     }
 
+    /// Start a new round with the same number of players and rules, clearing tehai,
+    /// sutehai, riichi and history, and refilling haiyama.
+    pub fn new_round(&mut self) -> &mut Self {
+        let riichi_ankan = self.riichi_ankan;
+        self.reinitialize(self.player_number);
+        self.riichi_ankan = riichi_ankan;
+        self
+    }
+
+    /// Return the number of players.
+    pub fn player_number(&self) -> PlayerNumber {
+        self.player_number
+    }
+
     /// Return a reference of haiyama
     pub fn haiyama(&self) -> &Haiyama {
         &self.haiyama
     }
 
-    /// Return a reference of the set within sutehai.
-    pub fn sutehai_type(&self) -> &BTreeSet<Hai> {
-        &self.sutehai_type
+    /// Return a reference of all sutehai in the order they are discarded.
+    pub fn sutehai(&self) -> &Vec<Hai> {
+        &self.sutehai
+    }
+
+    /// Return the summary of sutehai.
+    pub fn discard_profile(&self) -> DiscardProfile {
+        let mut suit = [0; 4];
+        for hai in &self.sutehai {
+            suit[hai.suit() as usize] += 1;
+        }
+        let early_jihai = self
+            .sutehai
+            .iter()
+            .take(EARLY_DISCARD_NUMBER)
+            .filter(|hai| hai.suit() == Suit::Jihai)
+            .count();
+        let tsumogiri = self
+            .history
+            .iter()
+            .filter(|(op, _, _)| {
+                matches!(
+                    op,
+                    Operation::Tehai(TehaiOperation::Discard {
+                        tsumogiri: true,
+                        ..
+                    }) | Operation::Tehai(TehaiOperation::Riichi {
+                        tsumogiri: true,
+                        ..
+                    })
+                )
+            })
+            .count();
+        DiscardProfile {
+            suit,
+            early_jihai,
+            tsumogiri,
+            riichi: self.riichi,
+        }
+    }
+
+    /// Return the set of types within sutehai.
+    pub fn sutehai_type(&self) -> BTreeSet<Hai> {
+        self.sutehai.iter().copied().collect()
     }
 
     /// Return operation history.
-    pub fn history(&self) -> &Vec<(Operation, State, BTreeSet<Hai>)> {
+    pub fn history(&self) -> &Vec<(Operation, State, Vec<Hai>)> {
         &self.history
     }
 
+    /// Return operation history of the kinds in kind_filter only, in order.
+    /// Riichi also discards a hai, so it is included by `OperationKind::TehaiDiscard`,
+    /// and is still `TehaiOperation::Riichi` in the result.
+    pub fn history_of(&self, kind_filter: &[OperationKind]) -> Vec<&(Operation, State, Vec<Hai>)> {
+        self.history
+            .iter()
+            .filter(|(op, _, _)| {
+                let kind = op.kind();
+                kind_filter.contains(&kind)
+                    || (kind == OperationKind::Riichi
+                        && kind_filter.contains(&OperationKind::TehaiDiscard))
+            })
+            .collect()
+    }
+
+    /// Return the number of hai added to tehai in history, including rinshanhai
+    /// added after kan but not rinshanhai given with kan.
+    pub fn draw_count(&self) -> u32 {
+        self.history
+            .iter()
+            .filter(|(op, _, _)| matches!(op, Operation::Tehai(TehaiOperation::Add { .. })))
+            .count() as u32
+    }
+
+    /// Return the index of the sutehai declaring riichi in `sutehai()`,
+    /// or None if riichi is not declared.
+    pub fn riichi(&self) -> Option<usize> {
+        self.riichi
+    }
+
+    /// Return true if ankan not changing machihai is allowed after riichi, true by default.
+    pub fn riichi_ankan(&self) -> bool {
+        self.riichi_ankan
+    }
+
+    /// Set whether ankan not changing machihai is allowed after riichi.
+    pub fn set_riichi_ankan(&mut self, allowed: bool) -> &mut Self {
+        self.riichi_ankan = allowed;
+        self
+    }
+
     /// Return the reference of tehai.
     pub fn tehai(&self) -> Option<&Tehai> {
         return self.tehai.as_ref();
     }
 
+    /// Return the hai just added to tehai if the last operation on tehai is adding,
+    /// or kan with rinshanhai.
+    ///
+    /// # Japanese
+    /// * tsumohai: ツモ牌
+    pub fn tsumohai(&self) -> Option<Hai> {
+        for (op, ..) in self.history.iter().rev() {
+            match op {
+                Operation::Tehai(TehaiOperation::Add { hai, .. }) => return Some(*hai),
+                Operation::Tehai(TehaiOperation::Naku {
+                    kind:
+                        Naku::Kan(Kan::Daiminkan { rinshanhai, .. })
+                        | Naku::Kan(Kan::Kakan { rinshanhai, .. })
+                        | Naku::Kan(Kan::Ankan { rinshanhai, .. }),
+                    ..
+                }) => return *rinshanhai,
+                Operation::Tehai(_) => return None,
+                _ => (),
+            }
+        }
+        None
+    }
+
+    /// Return the number of hai left in wall except wanpai, or None if not set.
+    pub fn live_wall_remaining(&self) -> Option<usize> {
+        self.live_wall_remaining
+    }
+
+    /// Set the number of hai left in wall except wanpai. Game manager only sees hai
+    /// drawn by self, so set it again after others draw. It then decreases by 1 for every
+    /// hai added to tehai, and for every kan with rinshanhai, as wanpai is refilled by
+    /// the last hai of wall. Undoing these operations restores it.
+    pub fn set_live_wall_remaining(&mut self, remaining: Option<usize>) -> &mut Self {
+        self.live_wall_remaining = remaining;
+        self
+    }
+
+    /// Return true if no hai is left in wall except wanpai, which means the hai just
+    /// added is the last one (haitei), or the hai just discarded is the last one (houtei).
+    /// Always false if `live_wall_remaining()` is not set.
+    ///
+    /// # Japanese
+    /// * haitei: 海底
+    /// * houtei: 河底
+    pub fn is_last_tile(&self) -> bool {
+        self.live_wall_remaining == Some(0)
+    }
+
     /// Return the analysis of tehai.
     pub fn tehai_analyze(&self) -> Result<(i32, Vec<MachiCondition>), String> {
         let tehai = self.tehai.as_ref().ok_or("Not initialized.".to_string())?;
         tehai.analyze(self.player_number, Some(&self))
     }
 
+    /// Return conditions of sutehai keeping tenpai like `tehai_analyze()`, but conditions
+    /// not furiten come before those furiten. Order is kept otherwise, and loss is not
+    /// changed. Return error if tehai cannot be tenpai by one discard.
+    pub fn tenpai_sutehai(&self) -> Result<Vec<MachiCondition>, String> {
+        let (shanten, mut conditions) = self.tehai_analyze()?;
+        if shanten != 0 {
+            return Err("Not tenpai after discard.".to_string());
+        }
+        conditions.sort_by_key(|condition| condition.furiten);
+        Ok(conditions)
+    }
+
+    /// Return the kind of furiten of tehai.
+    /// Only tehai lack of one hai can be furiten. Temporary furiten lasts until next discard.
+    pub fn furiten_kind(&self) -> FuritenKind {
+        let machi = match (self.state, &self.tehai) {
+            (State::LackOneHai, Some(tehai)) => match tehai.machi(self.player_number) {
+                Ok(machi) => machi,
+                Err(_) => return FuritenKind::None,
+            },
+            _ => return FuritenKind::None,
+        };
+
+        if self.sutehai.iter().any(|hai| machi.contains(hai)) {
+            return FuritenKind::Permanent;
+        }
+        for (op, ..) in self.history.iter().rev() {
+            match op {
+                Operation::Tehai(TehaiOperation::Discard { .. })
+                | Operation::Tehai(TehaiOperation::Riichi { .. }) => break,
+                Operation::Haiyama {
+                    kind: HaiyamaOperation::Discard(hai_vec),
+                    ..
+                } if hai_vec.iter().any(|hai| machi.contains(hai)) => {
+                    return FuritenKind::Temporary;
+                }
+                _ => (),
+            }
+        }
+        FuritenKind::None
+    }
+
     /// Main function to control the game.
     pub fn operate(&mut self, mut op: Operation) -> Result<(), String> {
         let last_state = self.state;
+        let last_sutehai = self.sutehai.clone();
+        self.check_riichi_lock(&op)?;
         match last_state {
             State::WaitToInit => self.operate_wait_to_init(&op)?,
             State::FullHai => self.operate_full_hai(&mut op)?,
             State::LackOneHai => self.operate_lack_one_hai(&mut op)?,
             State::WaitForRinshanhai => self.operate_wait_for_rinshanhai(&op)?,
         }
-        self.history
-            .push((op, last_state, self.sutehai_type.clone()));
+        if Self::uses_live_wall(&op) {
+            self.live_wall_remaining = self
+                .live_wall_remaining
+                .map(|remaining| remaining.saturating_sub(1));
+        }
+        self.history.push((op, last_state, last_sutehai));
+        debug_assert!(self.check_tile_total());
         Ok(())
     }
 
+    /// Return true if op takes a hai from wall except wanpai.
+    fn uses_live_wall(op: &Operation) -> bool {
+        match op {
+            Operation::Tehai(TehaiOperation::Add { .. }) => true,
+            Operation::Tehai(TehaiOperation::Naku {
+                kind:
+                    Naku::Kan(Kan::Daiminkan { rinshanhai, .. })
+                    | Naku::Kan(Kan::Kakan { rinshanhai, .. })
+                    | Naku::Kan(Kan::Ankan { rinshanhai, .. })
+                    | Naku::Kan(Kan::Unknown { rinshanhai, .. }),
+                ..
+            }) => rinshanhai.is_some(),
+            _ => false,
+        }
+    }
+
+    /// Return a clone of self with op operated, and self is not changed.
+    pub fn preview(&self, op: &Operation) -> Result<GameManager, String> {
+        let mut game_manager = self.clone();
+        game_manager.operate(op.clone())?;
+        Ok(game_manager)
+    }
+
     /// Undo last operation.
     pub fn back(&mut self, haiyama_sensitive: bool) -> Result<(Operation, State), String> {
-        let (op, last_state, sutehai_type) = self
+        let (op, last_state, sutehai) = self
             .history
             .pop()
             .ok_or("No more operation history.".to_string())?;
@@ -283,21 +717,232 @@ impl GameManager {
             State::WaitForRinshanhai => self.back_wait_for_rinshanhai(&op, haiyama_sensitive),
         } {
             Ok(_) => {
+                if Self::uses_live_wall(&op) {
+                    self.live_wall_remaining =
+                        self.live_wall_remaining.map(|remaining| remaining + 1);
+                }
                 self.state = last_state;
-                self.sutehai_type = sutehai_type;
+                self.sutehai = sutehai;
+                debug_assert!(self.check_tile_total());
                 Ok((op, last_state))
             }
             Err(error) => {
-                self.history.push((op, last_state, sutehai_type));
+                self.history.push((op, last_state, sutehai));
                 Err(error)
             }
         }
     }
 
+    /// Replay ops on a new game manager and compare with history of expected.
+    /// Return the index of the first divergent operation and description of it,
+    /// including failed operation, different operation or state, and missing operation.
+    pub fn verify_replay(expected: &GameManager, ops: &[Operation]) -> Result<(), (usize, String)> {
+        let mut game_manager = GameManager::new(expected.player_number);
+        for (index, op) in ops.iter().enumerate() {
+            if let Err(error) = game_manager.operate(op.clone()) {
+                return Err((index, format!("Failed to operate: {}", error)));
+            }
+            let (replayed_op, replayed_state, _) = &game_manager.history[index];
+            match expected.history.get(index) {
+                Some((expected_op, expected_state, _)) => {
+                    if replayed_op.to_json() != expected_op.to_json() {
+                        return Err((
+                            index,
+                            format!(
+                                "Expect operation {}, but {} found.",
+                                expected_op.to_json(),
+                                replayed_op.to_json()
+                            ),
+                        ));
+                    }
+                    if replayed_state != expected_state {
+                        return Err((
+                            index,
+                            format!(
+                                "Expect operating at state {:?}, but at {:?}.",
+                                expected_state, replayed_state
+                            ),
+                        ));
+                    }
+                }
+                None => {
+                    return Err((
+                        index,
+                        format!("Unexpected operation {}.", replayed_op.to_json()),
+                    ))
+                }
+            }
+        }
+        if let Some((expected_op, ..)) = expected.history.get(ops.len()) {
+            return Err((
+                ops.len(),
+                format!("Missing operation {}.", expected_op.to_json()),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Merge consecutive operations on haiyama of the same kind and the same
+    /// `haiyama_sensitive` into one operation. The state of game is not changed,
+    /// but `back()` will undo a merged operation at once.
+    pub fn compact_history(&mut self) {
+        let mut history: Vec<(Operation, State, Vec<Hai>)> = vec![];
+        for (op, state, sutehai) in self.history.drain(..) {
+            if let (
+                Some((
+                    Operation::Haiyama {
+                        kind: last_kind,
+                        haiyama_sensitive: last_sensitive,
+                    },
+                    ..,
+                )),
+                Operation::Haiyama {
+                    kind,
+                    haiyama_sensitive,
+                },
+            ) = (history.last_mut(), &op)
+            {
+                match (last_kind, kind) {
+                    (HaiyamaOperation::Add(last_vec), HaiyamaOperation::Add(hai_vec))
+                    | (HaiyamaOperation::Discard(last_vec), HaiyamaOperation::Discard(hai_vec))
+                        if last_sensitive == haiyama_sensitive =>
+                    {
+                        last_vec.extend(hai_vec);
+                        continue;
+                    }
+                    _ => (),
+                }
+            }
+            history.push((op, state, sutehai));
+        }
+        self.history = history;
+    }
+
+    /// Parse a line of command to operation.
+    ///
+    /// A command is a verb followed by arguments split by whitespace,
+    /// hai are written in the same way as tehai input, e.g. `discard 3m`.
+    /// * `init <tehai>`
+    /// * `draw <hai>`
+    /// * `discard <hai>`
+    /// * `riichi <hai>`
+    /// * `seen <hai...>`: hai known to be out of haiyama
+    /// * `chii <juntsu> <nakihai>`
+    /// * `pon <hai>`
+    /// * `kan | daiminkan | kakan | ankan <hai> [rinshanhai]`: kind of kan is resolved by
+    ///   game manager
+    pub fn parse_command(line: &str, player_number: PlayerNumber) -> Result<Operation, String> {
+        fn single_hai(string: &str, player_number: PlayerNumber) -> Result<Hai, String> {
+            let hai_vec = Hai::from_string_unordered(string, player_number)?;
+            if hai_vec.len() == 1 {
+                Ok(hai_vec[0])
+            } else {
+                Err(format!("'{}' is not a single hai.", string))
+            }
+        }
+
+        let mut words = line.split_whitespace();
+        let verb = words.next().ok_or("Empty command.".to_string())?;
+        let args: Vec<&str> = words.collect();
+        let arg = |index: usize| {
+            args.get(index)
+                .copied()
+                .ok_or(format!("Missing argument of '{}'.", verb))
+        };
+        let arg_number = match verb {
+            "init" | "draw" | "discard" | "riichi" | "seen" | "pon" => 1,
+            "chii" => 2,
+            "kan" | "daiminkan" | "kakan" | "ankan" => args.len().clamp(1, 2),
+            _ => return Err(format!("Unresolved command: {}.", line)),
+        };
+        if args.len() != arg_number {
+            return Err(format!("Wrong number of arguments of '{}'.", verb));
+        }
+
+        let op = match verb {
+            "init" => Operation::Tehai(TehaiOperation::Initialize(Tehai::new(
+                arg(0)?,
+                player_number,
+            )?)),
+            "draw" => Operation::Tehai(TehaiOperation::Add {
+                hai: single_hai(arg(0)?, player_number)?,
+                haiyama_sensitive: true,
+            }),
+            "discard" => Operation::Tehai(TehaiOperation::Discard {
+                hai: single_hai(arg(0)?, player_number)?,
+                tsumogiri: false,
+            }),
+            "riichi" => Operation::Tehai(TehaiOperation::Riichi {
+                hai: single_hai(arg(0)?, player_number)?,
+                tsumogiri: false,
+            }),
+            "seen" => Operation::Haiyama {
+                kind: HaiyamaOperation::Discard(Hai::from_string_unordered(
+                    arg(0)?,
+                    player_number,
+                )?),
+                haiyama_sensitive: true,
+            },
+            "chii" => {
+                let hai_vec = Hai::from_string_unordered(arg(0)?, player_number)?;
+                let juntsu = match Mentsu::new(&hai_vec, player_number) {
+                    Some(juntsu @ Mentsu::Juntsu(..)) => juntsu,
+                    _ => return Err(format!("'{}' is not a valid juntsu.", arg(0)?)),
+                };
+                Operation::Tehai(TehaiOperation::Naku {
+                    kind: Naku::Chii {
+                        juntsu,
+                        nakihai: single_hai(arg(1)?, player_number)?,
+                    },
+                    haiyama_sensitive: true,
+                })
+            }
+            "pon" => Operation::Tehai(TehaiOperation::Naku {
+                kind: Naku::Pon(Mentsu::Koutsu(single_hai(arg(0)?, player_number)?)),
+                haiyama_sensitive: true,
+            }),
+            _ => Operation::Tehai(TehaiOperation::Naku {
+                kind: Naku::Kan(Kan::Unknown {
+                    kantsu: Mentsu::Kantsu(single_hai(arg(0)?, player_number)?),
+                    rinshanhai: match args.get(1) {
+                        Some(rinshanhai) => Some(single_hai(rinshanhai, player_number)?),
+                        None => None,
+                    },
+                }),
+                haiyama_sensitive: true,
+            }),
+        };
+        Ok(op)
+    }
+
+    /// Parse a line of command and operate it. See `parse_command()` for commands.
+    /// For `daiminkan`, `kakan` and `ankan`, it is an error if the resolved kind mismatches.
+    pub fn command(&mut self, line: &str) -> Result<(), String> {
+        let op = Self::parse_command(line, self.player_number)?;
+        self.operate(op)?;
+
+        let expected_kind = match line.split_whitespace().next() {
+            Some("daiminkan") => OperationKind::Daiminkan,
+            Some("kakan") => OperationKind::Kakan,
+            Some("ankan") => OperationKind::Ankan,
+            _ => return Ok(()),
+        };
+        let kind = self.history.last().unwrap().0.kind();
+        if kind != expected_kind {
+            self.back(true)?;
+            return Err(format!("Expect {:?}, but it is {:?}.", expected_kind, kind));
+        }
+        Ok(())
+    }
+
     /// Print self to json.
     pub fn to_json(&self) -> serde_json::Value {
+        let mut sutehai_string_vec = vec![];
+        for hai in self.sutehai.iter() {
+            sutehai_string_vec.push(hai.to_string());
+        }
         let mut sutehai_type_string_vec = vec![];
-        for hai in self.sutehai_type.iter() {
+        for hai in self.sutehai_type().iter() {
             sutehai_type_string_vec.push(hai.to_string());
         }
 
@@ -307,12 +952,93 @@ impl GameManager {
         };
 
         json!({
+            "version": JSON_VERSION,
             "haiyama": self.haiyama.to_json(),
+            "sutehai": json!(sutehai_string_vec),
             "sutehai_type": json!(sutehai_type_string_vec),
             "tehai": tehai_json,
+            "riichi": self.riichi,
         })
     }
 
+    /// Check if json printed by `to_json()` is of a compatible version.
+    /// Json without version is taken as version 1.
+    pub fn check_json_version(json: &serde_json::Value) -> Result<(), String> {
+        let version = match json.get("version") {
+            None => 1,
+            Some(version) => version
+                .as_u64()
+                .ok_or(format!("Invalid version {}.", version))?,
+        };
+        if version > JSON_VERSION {
+            return Err(format!(
+                "Unsupported version {}, the latest supported version is {}.",
+                version, JSON_VERSION
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check if the number of hai on hand matches current state.
+    fn check_tile_total(&self) -> bool {
+        match (self.state, &self.tehai) {
+            (State::WaitToInit, None) => true,
+            (State::FullHai, Some(tehai)) => tehai.tile_total() == 14,
+            (State::LackOneHai, Some(tehai)) | (State::WaitForRinshanhai, Some(tehai)) => {
+                tehai.tile_total() == 13
+            }
+            _ => false,
+        }
+    }
+
+    /// Check if operation is allowed after riichi. Only tsumogiri, and ankan by the hai
+    /// just added which does not change machihai if `riichi_ankan()`, are allowed.
+    fn check_riichi_lock(&self, op: &Operation) -> Result<(), String> {
+        if self.riichi.is_none() {
+            return Ok(());
+        }
+        match (self.state, op) {
+            (State::FullHai, Operation::Tehai(TehaiOperation::Discard { hai, .. }))
+                if self.tsumohai() != Some(*hai) =>
+            {
+                return Err("Only tsumogiri is allowed after riichi.".to_string());
+            }
+            (
+                State::FullHai,
+                Operation::Tehai(TehaiOperation::Naku {
+                    kind:
+                        Naku::Kan(Kan::Unknown {
+                            kantsu: Mentsu::Kantsu(hai),
+                            ..
+                        }),
+                    ..
+                }),
+            ) => {
+                if !self.riichi_ankan {
+                    return Err("Ankan is not allowed after riichi.".to_string());
+                }
+                if self.tsumohai() != Some(*hai) {
+                    return Err(
+                        "Only ankan by the hai just added is allowed after riichi.".to_string()
+                    );
+                }
+                if self
+                    .tehai
+                    .as_ref()
+                    .unwrap()
+                    .kan_changes_wait(hai, self.player_number)?
+                {
+                    return Err("Ankan changing machihai is not allowed after riichi.".to_string());
+                }
+            }
+            (_, Operation::Tehai(TehaiOperation::Naku { .. })) => {
+                return Err("Only ankan is allowed after riichi.".to_string());
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
     fn operate_wait_to_init(&mut self, op: &Operation) -> Result<(), String> {
         fn operate_tehai_init(self_: &mut GameManager, tehai: &Tehai) -> Result<(), String> {
             if tehai.fuuro.len() != 0 {
@@ -374,9 +1100,37 @@ impl GameManager {
 
     fn operate_full_hai(&mut self, op: &mut Operation) -> Result<(), String> {
         match &*op {
-            Operation::Tehai(TehaiOperation::Discard(hai)) => {
-                self.tehai.as_mut().unwrap().discard(hai)?;
+            Operation::Tehai(TehaiOperation::Discard { hai, .. }) => {
+                let hai = *hai;
+                self.tehai.as_mut().unwrap().discard(&hai)?;
+                self.sutehai.push(hai);
+                self.state = State::LackOneHai;
+                *op = Operation::Tehai(TehaiOperation::Discard {
+                    hai,
+                    tsumogiri: self.tsumohai() == Some(hai),
+                });
+            }
+            Operation::Tehai(TehaiOperation::Riichi { hai, .. }) => {
+                let hai = *hai;
+                if self.riichi.is_some() {
+                    return Err("Riichi has already been declared.".to_string());
+                }
+                let mut tehai = self.tehai.clone().unwrap();
+                if !tehai.is_closed() {
+                    return Err("Cannot declare riichi with naku.".to_string());
+                }
+                tehai.discard(&hai)?;
+                if tehai.machi(self.player_number)?.is_empty() {
+                    return Err(format!("Tehai is not tenpai after discarding {}.", hai));
+                }
+                self.tehai = Some(tehai);
+                self.riichi = Some(self.sutehai.len());
+                self.sutehai.push(hai);
                 self.state = State::LackOneHai;
+                *op = Operation::Tehai(TehaiOperation::Riichi {
+                    hai,
+                    tsumogiri: self.tsumohai() == Some(hai),
+                });
             }
             Operation::Tehai(TehaiOperation::Naku {
                 kind: Naku::Kan(Kan::Unknown { kantsu, rinshanhai }),
@@ -665,9 +1419,14 @@ impl GameManager {
 
     fn back_full_hai(&mut self, op: &Operation, haiyama_sensitive: bool) -> Result<(), String> {
         match op {
-            Operation::Tehai(TehaiOperation::Discard(hai)) => {
+            Operation::Tehai(TehaiOperation::Discard { hai, .. }) => {
+                self.tehai.as_mut().unwrap().juntehai.push(*hai);
+                self.tehai.as_mut().unwrap().juntehai.sort();
+            }
+            Operation::Tehai(TehaiOperation::Riichi { hai, .. }) => {
                 self.tehai.as_mut().unwrap().juntehai.push(*hai);
                 self.tehai.as_mut().unwrap().juntehai.sort();
+                self.riichi = None;
             }
             Operation::Tehai(TehaiOperation::Naku {
                 kind: Naku::Kan(kan),
@@ -875,10 +1634,11 @@ impl GameManager {
 impl std::fmt::Display for GameManager {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut sutehai_type_string = "".to_string();
-        if self.sutehai_type.len() == 0 {
+        let sutehai_type = self.sutehai_type();
+        if sutehai_type.is_empty() {
             sutehai_type_string += "無し";
         } else {
-            for hai in self.sutehai_type.iter() {
+            for hai in sutehai_type.iter() {
                 sutehai_type_string += &hai.to_string();
                 sutehai_type_string += " ";
             }
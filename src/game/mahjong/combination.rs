@@ -8,11 +8,17 @@ use serde_json::json;
 /// Juntsu: 順子
 /// Koutsu: 刻子
 /// Kantsu: 槓子
+/// Ankantsu: 暗槓子
+///
+/// # Member
+/// * Kantsu: kantsu formed by daiminkan or kakan, or given as input of kan.
+/// * Ankantsu: kantsu formed by ankan in fuuro, which does not open tehai.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Mentsu {
     Juntsu(Hai, Hai, Hai),
     Koutsu(Hai),
     Kantsu(Hai),
+    Ankantsu(Hai),
 }
 
 /// Two different hai wait for one hai.
@@ -122,6 +128,15 @@ impl Mentsu {
                     "hai":hai_string_vec
                 })
             }
+            Mentsu::Ankantsu(a) => {
+                for _ in 0..4 {
+                    hai_string_vec.push(a.to_string());
+                }
+                json!({
+                    "type":"ankantsu",
+                    "hai":hai_string_vec
+                })
+            }
         }
     }
 }
@@ -143,6 +158,10 @@ impl std::fmt::Display for Mentsu {
                     let tile = a.to_string();
                     format!("[{}{}{}{}]", tile, tile, tile, tile)
                 }
+                Mentsu::Ankantsu(a) => {
+                    let tile = a.to_string();
+                    format!("({}{}{}{})", tile, tile, tile, tile)
+                }
             }
         )
     }
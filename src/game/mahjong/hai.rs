@@ -23,6 +23,21 @@ pub enum Hai {
     Jihai(u8),
 }
 
+/// Suit of hai, without number of it.
+///
+/// # Japanese
+/// * Manzu: 萬子
+/// * Pinzu: 筒子
+/// * Souzu: 索子
+/// * Jihai: 字牌
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Suit {
+    Manzu,
+    Pinzu,
+    Souzu,
+    Jihai,
+}
+
 impl Hai {
     /// Parse string to a vec of hai. Order of hai is equal with input string.
     pub fn from_string_unordered(string: &str, player_number: PlayerNumber) -> Result<Vec<Hai>, String> {
@@ -93,6 +108,39 @@ impl Hai {
         }
     }
 
+    /// Return suit of hai.
+    pub fn suit(&self) -> Suit {
+        match self {
+            Hai::Manzu(_) => Suit::Manzu,
+            Hai::Pinzu(_) => Suit::Pinzu,
+            Hai::Souzu(_) => Suit::Souzu,
+            Hai::Jihai(_) => Suit::Jihai,
+        }
+    }
+
+    /// Return number of hai, or None for jihai.
+    pub fn number(&self) -> Option<u8> {
+        match self {
+            Hai::Manzu(num) | Hai::Pinzu(num) | Hai::Souzu(num) => Some(*num),
+            Hai::Jihai(_) => None,
+        }
+    }
+
+    /// Return true if it is sangenpai -- 5\~7z, or the same as bakaze or jikaze.
+    ///
+    /// # Japanese
+    /// * yakuhai: 役牌
+    /// * sangenpai: 三元牌
+    /// * bakaze: 場風
+    /// * jikaze: 自風
+    pub fn is_yakuhai(&self, bakaze: Hai, jikaze: Hai) -> bool {
+        match self {
+            Hai::Jihai(5..=7) => true,
+            Hai::Jihai(_) => *self == bakaze || *self == jikaze,
+            _ => false,
+        }
+    }
+
     /// Return ture when **all** hai in iterator is valid. Otherwise return false.
     pub fn check_iter_valid<'a, T>(iter: T, player_number: PlayerNumber) -> bool
     where
@@ -152,6 +200,28 @@ impl Hai {
         all_hai_type
     }
 
+    /// Return index of hai in 34 types of hai -- 0\~8 for 1\~9m, 9\~17 for 1\~9p,
+    /// 18\~26 for 1\~9s and 27\~33 for 1\~7z.
+    pub fn to_index(self) -> u8 {
+        match self {
+            Hai::Manzu(num) => num - 1,
+            Hai::Pinzu(num) => num + 8,
+            Hai::Souzu(num) => num + 17,
+            Hai::Jihai(num) => num + 26,
+        }
+    }
+
+    /// Return hai of index in 34 types of hai. See `to_index()`.
+    pub fn from_index(index: u8) -> Option<Hai> {
+        match index {
+            0..=8 => Some(Hai::Manzu(index + 1)),
+            9..=17 => Some(Hai::Pinzu(index - 8)),
+            18..=26 => Some(Hai::Souzu(index - 17)),
+            27..=33 => Some(Hai::Jihai(index - 26)),
+            _ => None,
+        }
+    }
+
     /// Return previous hai. It means, like 1m for 2m.
     ///
     /// # Parameters
@@ -317,3 +387,26 @@ impl std::fmt::Display for Hai {
         )
     }
 }
+
+/// Serialize hai as the string form, such as `"5z"`.
+impl serde::Serialize for Hai {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Deserialize hai from the string form of exactly one hai, such as `"5z"`.
+impl<'de> serde::Deserialize<'de> for Hai {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        match Hai::from_string_unordered(&string, PlayerNumber::Four)
+            .map_err(serde::de::Error::custom)?[..]
+        {
+            [hai] => Ok(hai),
+            _ => Err(serde::de::Error::custom(format!(
+                "{} is not exactly one hai.",
+                string
+            ))),
+        }
+    }
+}
@@ -21,6 +21,56 @@ impl Haiyama {
         Self { map }
     }
 
+    /// Create a haiyama with the number of each type of hai, indexed 0\~8 for 1\~9m,
+    /// 9\~17 for 1\~9p, 18\~26 for 1\~9s and 27\~33 for 1\~7z. Each number is limited
+    /// to 4, and must be 0 for 2\~8m on 3-players mode.
+    pub fn from_counts(counts: &[u8; 34], player_number: PlayerNumber) -> Result<Self, String> {
+        let mut map = BTreeMap::new();
+        for (index, number) in counts.iter().enumerate() {
+            // Index is always less than 34.
+            let hai = Hai::from_index(index as u8).unwrap();
+            if !hai.is_valid(player_number) {
+                if *number > 0 {
+                    return Err(format!("'{}' is invalid hai.", hai));
+                }
+            } else if *number > 4 {
+                return Err(format!("Cannot have {} '{}' in haiyama.", number, hai));
+            } else {
+                map.insert(hai, *number);
+            }
+        }
+        Ok(Self { map })
+    }
+
+    /// Return the number of all hai in haiyama.
+    pub fn remaining(&self) -> u32 {
+        self.map.values().map(|number| *number as u32).sum()
+    }
+
+    /// Return the number of hai in haiyama of each suit -- manzu, pinzu, souzu and jihai.
+    pub fn remaining_by_suit(&self) -> [u32; 4] {
+        let mut remaining = [0; 4];
+        for (hai, number) in &self.map {
+            remaining[hai.suit() as usize] += *number as u32;
+        }
+        remaining
+    }
+
+    /// Return all types of hai with none left in haiyama.
+    pub fn dead_tiles(&self) -> Vec<Hai> {
+        self.map
+            .iter()
+            .filter(|(_, number)| **number == 0)
+            .map(|(hai, _)| *hai)
+            .collect()
+    }
+
+    /// Return true if at least one of hai is left in haiyama.
+    /// Always false for hai not in haiyama, such as 2\~8m on 3-players mode.
+    pub fn can_draw(&self, hai: &Hai) -> bool {
+        self.map.get(hai).is_some_and(|number| *number > 0)
+    }
+
     /// Add one hai to haiyama, limited to 4.
     pub fn add(&mut self, hai: &Hai) -> Result<(), String> {
         let number = self.map[hai];
@@ -101,6 +151,12 @@ impl Haiyama {
         Ok(())
     }
 
+    /// Discard all hai seen on the table, such as sutehai of others and dora indicators,
+    /// from haiyama. Haiyama is not changed if any of them is not enough.
+    pub fn mark_seen_many(&mut self, tiles: &[Hai]) -> Result<(), String> {
+        self.discard_with_vec(&tiles.to_vec(), true)
+    }
+
     /// Print self to json.
     pub fn to_json(&self) -> serde_json::Value {
         let mut json_vec = vec![];
@@ -4,10 +4,13 @@ mod haiyama;
 mod player_number;
 mod tehai;
 
-use super::{GameManager, Kan};
+use super::{GameManager, Kan, Naku};
 
 pub use combination::{Mentsu, Taatsu, Toitsu, Ukihai};
-pub use hai::Hai;
+pub use hai::{Hai, Suit};
 pub use haiyama::Haiyama;
 pub use player_number::PlayerNumber;
-pub use tehai::{MachiCondition, Tehai};
+pub use tehai::{
+    BlockReport, CallAnalysis, HandArchetype, ImprovementKind, MachiCondition, ShantenBreakdown,
+    Tehai, TieBreak, UkeireTree, TENPAI_PROBABILITY_MAX_DRAWS, UKEIRE_TREE_MAX_DEPTH,
+};
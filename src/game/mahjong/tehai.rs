@@ -1,6 +1,6 @@
-use super::{GameManager, Hai, Kan, Mentsu, PlayerNumber, Taatsu, Toitsu, Ukihai};
+use super::{GameManager, Hai, Haiyama, Kan, Mentsu, Naku, PlayerNumber, Taatsu, Toitsu, Ukihai};
 use serde_json::json;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 /// hai on hand.
 ///
@@ -11,7 +11,7 @@ use std::collections::{BTreeMap, HashMap, HashSet};
 ///
 /// # Member
 /// * juntehai: Vec of hai which not formed mentsu.
-/// * fuuro: Mentsu which already formed.
+/// * fuuro: Mentsu which already formed, with kantsu formed by ankan as `Mentsu::Ankantsu`.
 ///
 /// # Examples
 /// ```rust
@@ -70,13 +70,140 @@ struct Decomposer {
 /// * sutehai: which ukihai will be discarded.
 /// * machihai: hai waiting for.
 /// * furiten: if machihai included prevenient sutehai.
-#[derive(Clone, Debug)]
+/// * loss: how many machihai less than the best sutehai.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct MachiCondition {
     pub sutehai: Hai,
     pub machihai: BTreeMap<Hai, u8>,
     pub furiten: bool,
+    pub loss: usize,
 }
 
+/// Apparent direction of tehai.
+///
+/// # Member
+/// * Standard: mentsute.
+/// * Chiitoitsu: chiitoitsu has less shanten than mentsute.
+/// * Honitsu: mentsute with at most 3 hai out of one suit and jihai, including chinitsu.
+/// * Kokushimusou: kokushimusou has less shanten than mentsute.
+///
+/// # Japanese
+/// * Honitsu: 混一色
+/// * chinitsu: 清一色
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HandArchetype {
+    Standard,
+    Chiitoitsu,
+    Honitsu,
+    Kokushimusou,
+}
+
+/// Blocks of tehai in mentsute form, from a decomposition with minimum shanten.
+///
+/// # Member
+/// * mentsu: number of mentsu, including fuuro.
+/// * toitsu: number of toitsu.
+/// * taatsu: number of taatsu.
+/// * ukihai: number of ukihai.
+/// * target: number of blocks needed for hourakei, 4 mentsu and 1 toitsu for 14 hai.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BlockReport {
+    pub mentsu: usize,
+    pub toitsu: usize,
+    pub taatsu: usize,
+    pub ukihai: usize,
+    pub target: usize,
+}
+
+/// Shanten of each hourakei.
+///
+/// # Member
+/// * mentsute: shanten of mentsute.
+/// * chiitoitsu: shanten of chiitoitsu, None if not 14 juntehai without fuuro.
+/// * kokushimusou: shanten of kokushimusou, None if not 14 juntehai without fuuro.
+/// * min: the minimum of all.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ShantenBreakdown {
+    pub mentsute: i32,
+    pub chiitoitsu: Option<i32>,
+    pub kokushimusou: Option<i32>,
+    pub min: i32,
+}
+
+/// Shanten and ukeire of a 3*k+1 juntehai before and after chii, pon or daiminkan.
+///
+/// # Japanese
+/// * ukeire: 受け入れ
+///
+/// # Member
+/// * shanten: shanten before naku.
+/// * ukeire: the number of hai reducing shanten before naku.
+/// * naku_shanten: shanten after naku and discarding sutehai.
+/// * naku_ukeire: the number of hai reducing shanten after naku and discarding sutehai.
+/// * sutehai: the best sutehai after naku, None if hourakei after naku or for daiminkan.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CallAnalysis {
+    pub shanten: i32,
+    pub ukeire: u32,
+    pub naku_shanten: i32,
+    pub naku_ukeire: u32,
+    pub sutehai: Option<Hai>,
+}
+
+impl CallAnalysis {
+    /// Return true if naku reduces shanten.
+    pub fn advances(&self) -> bool {
+        self.naku_shanten < self.shanten
+    }
+}
+
+/// Effect of drawing a hai to a 3*k+1 juntehai.
+///
+/// # Member
+/// * ShantenAdvance: shanten is reduced.
+/// * ShapeImprove: shanten is kept, but ukeire becomes more after the best sutehai.
+/// * Useless: otherwise.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImprovementKind {
+    ShantenAdvance,
+    ShapeImprove,
+    Useless,
+}
+
+/// Order of conditions having the same number of machihai.
+///
+/// # Member
+/// * HaiOrder: by order of sutehai -- manzu, pinzu, souzu, jihai, and by number.
+/// * YaochuupaiFirst: discard jihai first, then 1 and 9, 2 and 8, and others at last.
+///   The same as `HaiOrder` inside each group.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TieBreak {
+    HaiOrder,
+    YaochuupaiFirst,
+}
+
+/// Tree of ukeire for a few draws ahead.
+///
+/// # Japanese
+/// * ukeire: 受け入れ
+///
+/// # Member
+/// * shanten: shanten number of tehai.
+/// * children: for each sutehai and machihai in analysis of tehai, the number of the
+///   machihai in haiyama and the tree of tehai after discarding sutehai and adding
+///   machihai. Empty when tehai is hourakei or depth is reached.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UkeireTree {
+    pub shanten: i32,
+    pub children: BTreeMap<(Hai, Hai), (u8, UkeireTree)>,
+}
+
+/// Max depth of `Tehai::ukeire_tree()`.
+pub const UKEIRE_TREE_MAX_DEPTH: u32 = 2;
+
+/// Max draws of `Tehai::tenpai_probability()`, the most draws of a player in a round.
+pub const TENPAI_PROBABILITY_MAX_DRAWS: u8 = 18;
+
 fn remove_once<T: Eq>(container: &mut Vec<T>, item: &T) {
     for (index, cur) in container.iter().enumerate() {
         if cur == item {
@@ -90,12 +217,14 @@ impl Tehai {
     /// Create tehai from string.
     ///
     /// # Input
-    /// You can input hai out of order, and use [] represent formed melds. All spaces will be ignored.
+    /// You can input hai out of order, and use [] represent formed melds, () represent kantsu
+    /// formed by ankan. All spaces will be ignored.
     /// (they will not be considered for shanten number).
     /// * stanard: `1m2m3m4m4m5m4p4p4p5p8s[1z1z1z]`
     /// * shorter: `123445m4445p8s[111z]`
     /// * with spaces: `123445m 4445p 8s [111z]`
     /// * chaos: `45p 8s14 4m[11 1z]2 5m44p 3m`
+    /// * with ankan: `123445m4445p(1111z)8s`
     ///
     /// # Examples
     /// ```rust
@@ -139,13 +268,26 @@ impl Tehai {
         fn handle_hai_in_mentsu_stash(
             char_index: usize,
             player_number: PlayerNumber,
+            ankan: bool,
             hai_in_mentsu_stash: &mut Vec<Hai>,
             output: &mut Vec<Mentsu>,
         ) -> Result<(), String> {
-            let mentsu = Mentsu::new(hai_in_mentsu_stash, player_number).ok_or(format!(
-                "Not a valid meld on '[]' before index {}.",
-                char_index
-            ))?;
+            let mentsu = match (Mentsu::new(hai_in_mentsu_stash, player_number), ankan) {
+                (Some(mentsu), false) => mentsu,
+                (Some(Mentsu::Kantsu(hai)), true) => Mentsu::Ankantsu(hai),
+                (_, false) => {
+                    return Err(format!(
+                        "Not a valid meld on '[]' before index {}.",
+                        char_index
+                    ))
+                }
+                (_, true) => {
+                    return Err(format!(
+                        "Not a valid kantsu on '()' before index {}.",
+                        char_index
+                    ))
+                }
+            };
 
             output.push(mentsu);
             hai_in_mentsu_stash.clear();
@@ -157,6 +299,7 @@ impl Tehai {
         let mut char_stash: Vec<char> = vec![];
         let mut hai_in_mentsu_stash: Vec<Hai> = vec![];
         let mut in_mentsu = false;
+        let mut in_ankan = false;
 
         for (index, chr) in string.chars().enumerate() {
             match chr {
@@ -180,31 +323,33 @@ impl Tehai {
                     }
                 }
                 '1'..='9' => char_stash.push(chr),
-                '[' => {
+                '[' | '(' => {
                     if in_mentsu {
-                        return Err(format!("Second '[' found at index {}.", index));
+                        return Err(format!("Second '{}' found at index {}.", chr, index));
                     }
                     if char_stash.len() > 0 {
                         return Err(format!(
-                            "Need 'm' 'p' 's' 'z' but find '[' at index {}.",
-                            index
+                            "Need 'm' 'p' 's' 'z' but find '{}' at index {}.",
+                            chr, index
                         ));
                     };
                     in_mentsu = true;
+                    in_ankan = chr == '(';
                 }
-                ']' => {
-                    if !in_mentsu {
-                        return Err(format!("Unmatched ']' found at index {}.", index));
+                ']' | ')' => {
+                    if !in_mentsu || in_ankan != (chr == ')') {
+                        return Err(format!("Unmatched '{}' found at index {}.", chr, index));
                     }
                     if char_stash.len() > 0 {
                         return Err(format!(
-                            "Need 'm' 'p' 's' 'z' but find ']' at index {}.",
-                            index
+                            "Need 'm' 'p' 's' 'z' but find '{}' at index {}.",
+                            chr, index
                         ));
                     };
                     handle_hai_in_mentsu_stash(
                         index,
                         player_number,
+                        in_ankan,
                         &mut hai_in_mentsu_stash,
                         &mut fuuro,
                     )?;
@@ -234,6 +379,106 @@ impl Tehai {
         }
     }
 
+    /// Create tehai without fuuro from the number of each type of hai, indexed
+    /// 0\~8 for 1\~9m, 9\~17 for 1\~9p, 18\~26 for 1\~9s and 27\~33 for 1\~7z.
+    pub fn from_counts(counts: &[u8; 34]) -> Result<Self, String> {
+        let mut juntehai = vec![];
+        for (index, number) in counts.iter().enumerate() {
+            // Index is always less than 34.
+            let hai = Hai::from_index(index as u8).unwrap();
+            if *number > 4 {
+                return Err(format!("Fifth {} found.", hai));
+            }
+            for _ in 0..*number {
+                juntehai.push(hai);
+            }
+        }
+
+        if juntehai.len() % 3 == 0 || juntehai.len() > 14 {
+            return Err(format!(
+                "The number of hai on hand must be 3*k+1 or 3*k+2 and no more than 14, \
+                but {} provided.",
+                juntehai.len()
+            ));
+        }
+
+        Ok(Self {
+            juntehai,
+            fuuro: vec![],
+        })
+    }
+
+    /// Create tehai without fuuro from numeric codes of hai, as in paifu of Mahjong Soul
+    /// exported to the tenhou.net/6 form -- 11\~19 for 1\~9m, 21\~29 for 1\~9p,
+    /// 31\~39 for 1\~9s, 41\~47 for 1\~7z, and 51, 52, 53 for aka 5m, 5p, 5s.
+    /// Aka hai are taken as normal 5.
+    ///
+    /// # Japanese
+    /// * aka: 赤
+    pub fn from_majsoul(codes: &[u8], player_number: PlayerNumber) -> Result<Self, String> {
+        let mut counts = [0; 34];
+        for code in codes {
+            let hai = match (code / 10, code % 10) {
+                (1, number @ 1..=9) => Hai::Manzu(number),
+                (2, number @ 1..=9) => Hai::Pinzu(number),
+                (3, number @ 1..=9) => Hai::Souzu(number),
+                (4, number @ 1..=7) => Hai::Jihai(number),
+                (5, 1) => Hai::Manzu(5),
+                (5, 2) => Hai::Pinzu(5),
+                (5, 3) => Hai::Souzu(5),
+                _ => return Err(format!("{} is not a code of hai.", code)),
+            };
+            if !hai.is_valid(player_number) {
+                return Err(format!("'{}' is invalid hai.", hai));
+            }
+            counts[hai.to_index() as usize] += 1;
+        }
+        Self::from_counts(&counts)
+    }
+
+    /// Return the number of each type of hai in juntehai. See `from_counts()` for
+    /// the order.
+    pub fn to_counts(&self) -> [u8; 34] {
+        let mut counts = [0; 34];
+        for hai in &self.juntehai {
+            counts[hai.to_index() as usize] += 1;
+        }
+        counts
+    }
+
+    /// Return the number of hai in juntehai and fuuro, counting kantsu as 4 hai.
+    pub fn tile_count(&self, hai: &Hai) -> u8 {
+        let in_juntehai = self.juntehai.iter().filter(|item| *item == hai).count() as u8;
+        let in_fuuro: u8 = self
+            .fuuro
+            .iter()
+            .map(|mentsu| match mentsu {
+                Mentsu::Juntsu(a, b, c) => {
+                    [a, b, c].iter().filter(|item| **item == hai).count() as u8
+                }
+                Mentsu::Koutsu(item) if item == hai => 3,
+                Mentsu::Kantsu(item) | Mentsu::Ankantsu(item) if item == hai => 4,
+                _ => 0,
+            })
+            .sum();
+        in_juntehai + in_fuuro
+    }
+
+    /// Return the number of hai to exchange for juntehai to become juntehai of target.
+    /// If they have different number of hai, it is the number of hai to add or discard
+    /// on the longer side. Fuuro are ignored.
+    pub fn distance_to(&self, target: &Tehai) -> u8 {
+        let (mut surplus, mut lack) = (0, 0);
+        for (number, target_number) in self.to_counts().iter().zip(target.to_counts().iter()) {
+            if number > target_number {
+                surplus += number - target_number;
+            } else {
+                lack += target_number - number;
+            }
+        }
+        surplus.max(lack)
+    }
+
     /// Analyze conditions of sutehai and machihai.
     ///
     /// # Return
@@ -244,6 +489,387 @@ impl Tehai {
         player_number: PlayerNumber,
         game_manager: Option<&GameManager>,
     ) -> Result<(i32, Vec<MachiCondition>), String> {
+        self.analyze_with_tie_break(player_number, game_manager, TieBreak::HaiOrder)
+    }
+
+    /// Analyze like `analyze()`, but order conditions with the same number of
+    /// machihai by tie_break.
+    pub fn analyze_with_tie_break(
+        &self,
+        player_number: PlayerNumber,
+        game_manager: Option<&GameManager>,
+        tie_break: TieBreak,
+    ) -> Result<(i32, Vec<MachiCondition>), String> {
+        match game_manager {
+            Some(game_manager) => self.analyze_core(
+                player_number,
+                Some(game_manager.haiyama()),
+                &game_manager.sutehai_type(),
+                tie_break,
+                &BTreeSet::new(),
+            ),
+            None => self.analyze_core(
+                player_number,
+                None,
+                &BTreeSet::new(),
+                tie_break,
+                &BTreeSet::new(),
+            ),
+        }
+    }
+
+    /// Analyze like `analyze()`, but never take hai in excluded as sutehai.
+    /// If every sutehai keeping the minimum shanten is excluded, the other sutehai are
+    /// ranked by shanten after discarding them, then by machihai. The returned shanten is
+    /// then the lowest one they keep, which may be higher than that of `analyze()`.
+    pub fn analyze_excluding(
+        &self,
+        player_number: PlayerNumber,
+        game_manager: Option<&GameManager>,
+        excluded: &BTreeSet<Hai>,
+    ) -> Result<(i32, Vec<MachiCondition>), String> {
+        let (haiyama, sutehai_type) = match game_manager {
+            Some(game_manager) => (Some(game_manager.haiyama()), game_manager.sutehai_type()),
+            None => (None, BTreeSet::new()),
+        };
+        let (shanten, conditions) = self.analyze_core(
+            player_number,
+            haiyama,
+            &sutehai_type,
+            TieBreak::HaiOrder,
+            excluded,
+        )?;
+        if shanten == -1 || !conditions.is_empty() {
+            return Ok((shanten, conditions));
+        }
+
+        let full_haiyama = Haiyama::new(player_number);
+        let sutehai_set: BTreeSet<Hai> = self
+            .juntehai
+            .iter()
+            .filter(|hai| !excluded.contains(hai))
+            .copied()
+            .collect();
+        let mut ranked = vec![];
+        for sutehai in sutehai_set {
+            let mut tehai = self.clone();
+            tehai.discard(&sutehai)?;
+            let (discarded_shanten, ukeire) =
+                tehai.shanten_and_ukeire(player_number, &full_haiyama)?;
+            let mut condition = MachiCondition::new(sutehai);
+            condition.machihai = ukeire;
+            condition.finally(self, haiyama, &sutehai_type);
+            ranked.push((discarded_shanten, condition));
+        }
+        ranked.sort_by(|(lhs_shanten, lhs), (rhs_shanten, rhs)| {
+            lhs_shanten
+                .cmp(rhs_shanten)
+                .then(lhs.nokori().cmp(&rhs.nokori()).reverse())
+                .then(lhs.sutehai.cmp(&rhs.sutehai))
+        });
+        let lowest = match ranked.first() {
+            Some((lowest, _)) => *lowest,
+            None => return Ok((shanten, conditions)),
+        };
+        let mut conditions: Vec<MachiCondition> = ranked
+            .into_iter()
+            .filter(|(discarded_shanten, _)| *discarded_shanten == lowest)
+            .map(|(_, condition)| condition)
+            .collect();
+        let best = conditions[0].nokori();
+        for condition in &mut conditions {
+            condition.loss = best - condition.nokori();
+        }
+        Ok((lowest, conditions))
+    }
+
+    /// Analyze conditions of sutehai and machihai like `analyze()`, but take the
+    /// number of machihai from haiyama. Furiten is not checked.
+    pub fn analyze_with_haiyama(
+        &self,
+        player_number: PlayerNumber,
+        haiyama: &Haiyama,
+    ) -> Result<(i32, Vec<MachiCondition>), String> {
+        self.analyze_core(
+            player_number,
+            Some(haiyama),
+            &BTreeSet::new(),
+            TieBreak::HaiOrder,
+            &BTreeSet::new(),
+        )
+    }
+
+    /// Analyze like `analyze_with_haiyama()`, and print tehai, shanten number and
+    /// conditions to json, the same as json output of the command line.
+    pub fn analysis_json(
+        &self,
+        player_number: PlayerNumber,
+        haiyama: &Haiyama,
+    ) -> Result<serde_json::Value, String> {
+        let (shanten, conditions) = self.analyze_with_haiyama(player_number, haiyama)?;
+        let mut condition_json_vec = vec![];
+        for condition in &conditions {
+            condition_json_vec.push(condition.to_json());
+        }
+        Ok(json!({
+            "tehai": self.to_json(),
+            "shanten_number": shanten,
+            "conditions": condition_json_vec
+        }))
+    }
+
+    /// Classify the apparent direction of a 3*k+2 juntehai. See `HandArchetype`.
+    pub fn archetype(&self, player_number: PlayerNumber) -> Result<HandArchetype, String> {
+        let (_, decomposers) = self.decompose(player_number)?;
+        let hourakei_set: HashSet<Hourakei> = decomposers
+            .iter()
+            .map(|decomposer| decomposer.hourakei)
+            .collect();
+        if !hourakei_set.contains(&Hourakei::Mentsute) {
+            if hourakei_set.contains(&Hourakei::Kokushimusou) {
+                return Ok(HandArchetype::Kokushimusou);
+            }
+            if hourakei_set.contains(&Hourakei::Chiitoitsu) {
+                return Ok(HandArchetype::Chiitoitsu);
+            }
+        }
+
+        // Number of hai of manzu, pinzu and souzu.
+        let mut suit_number = [0; 3];
+        let mut count = |hai: &Hai, number: usize| match hai {
+            Hai::Manzu(_) => suit_number[0] += number,
+            Hai::Pinzu(_) => suit_number[1] += number,
+            Hai::Souzu(_) => suit_number[2] += number,
+            Hai::Jihai(_) => (),
+        };
+        for hai in &self.juntehai {
+            count(hai, 1);
+        }
+        for mentsu in &self.fuuro {
+            match mentsu {
+                Mentsu::Juntsu(hai, ..)
+                | Mentsu::Koutsu(hai)
+                | Mentsu::Kantsu(hai)
+                | Mentsu::Ankantsu(hai) => count(hai, 3),
+            }
+        }
+        let total: usize = suit_number.iter().sum();
+        let max = *suit_number.iter().max().unwrap();
+        if total - max <= 3 {
+            Ok(HandArchetype::Honitsu)
+        } else {
+            Ok(HandArchetype::Standard)
+        }
+    }
+
+    /// Count the blocks of a 3*k+2 juntehai in mentsute form.
+    /// For decompositions with the same shanten, the one with more mentsu and blocks is taken.
+    pub fn block_analysis(&self, player_number: PlayerNumber) -> Result<BlockReport, String> {
+        let decomposer = self.block_decomposer(player_number)?;
+
+        Ok(BlockReport {
+            mentsu: decomposer.mentsu_vec.len() + self.fuuro.len(),
+            toitsu: decomposer.toitsu_vec.len(),
+            taatsu: decomposer.taatsu_vec.len(),
+            ukihai: decomposer.valid_ukihai_vec.len() + decomposer.invalid_ukihai_vec.len(),
+            target: (self.juntehai.len() + 1) / 3 + self.fuuro.len(),
+        })
+    }
+
+    /// Propose the target blocks of a 3*k+2 juntehai in mentsute form, the mentsu to
+    /// make and the toitsu as jantou, such as 4 mentsu and 1 toitsu for 14 hai.
+    /// Taatsu and extra toitsu are completed to mentsu, ryanmen first, then toitsu,
+    /// then kanchan and penchan. Fuuro are not included.
+    /// If there are not enough blocks, fewer mentsu are returned.
+    ///
+    /// # Japanese
+    /// * jantou: 雀頭
+    pub fn suggest_blocks(
+        &self,
+        player_number: PlayerNumber,
+    ) -> Result<(Vec<Mentsu>, Option<Toitsu>), String> {
+        let decomposer = self.block_decomposer(player_number)?;
+        let target = (self.juntehai.len() + 1) / 3 - 1;
+
+        let mut toitsu_vec = decomposer.toitsu_vec.clone();
+        let jantou = if toitsu_vec.is_empty() {
+            None
+        } else {
+            Some(toitsu_vec.remove(0))
+        };
+
+        // Candidates with the number of hai types completing them.
+        let mut candidates = vec![];
+        for Taatsu(lhs, rhs) in &decomposer.taatsu_vec {
+            let previous = lhs.previous(player_number, false);
+            let next = rhs.next(player_number, false);
+            if lhs.next(player_number, false) == Some(*rhs) {
+                match (previous, next) {
+                    (Some(_), Some(next)) => candidates.push((2, Mentsu::Juntsu(*lhs, *rhs, next))),
+                    (Some(previous), None) => {
+                        candidates.push((1, Mentsu::Juntsu(previous, *lhs, *rhs)))
+                    }
+                    (None, Some(next)) => candidates.push((1, Mentsu::Juntsu(*lhs, *rhs, next))),
+                    (None, None) => {}
+                }
+            } else if let Some(middle) = lhs.next(player_number, false) {
+                candidates.push((1, Mentsu::Juntsu(*lhs, middle, *rhs)));
+            }
+        }
+        for Toitsu(hai) in &toitsu_vec {
+            candidates.push((1, Mentsu::Koutsu(*hai)));
+        }
+        // Stable sort keeps toitsu before kanchan and penchan.
+        candidates.sort_by_key(|(wait, mentsu)| {
+            (
+                std::cmp::Reverse(*wait),
+                !matches!(mentsu, Mentsu::Koutsu(_)),
+            )
+        });
+
+        let mut mentsu_vec = decomposer.mentsu_vec.clone();
+        for (_, mentsu) in candidates {
+            if mentsu_vec.len() >= target {
+                break;
+            }
+            mentsu_vec.push(mentsu);
+        }
+        Ok((mentsu_vec, jantou))
+    }
+
+    /// Return all hai of toitsu which can be jantou in some decomposition of a 3*k+2 juntehai
+    /// in mentsute form with the minimum shanten, in order of hai.
+    /// Empty if only chiitoitsu or kokushimusou has the minimum shanten.
+    ///
+    /// # Japanese
+    /// * jantou: 雀頭
+    pub fn pair_candidates(&self, player_number: PlayerNumber) -> Result<Vec<Hai>, String> {
+        let (_, decomposers) = self.decompose(player_number)?;
+        let candidates: BTreeSet<Hai> = decomposers
+            .iter()
+            .filter(|decomposer| decomposer.hourakei == Hourakei::Mentsute)
+            .flat_map(|decomposer| decomposer.toitsu_vec.iter().map(|Toitsu(hai)| *hai))
+            .collect();
+        Ok(candidates.into_iter().collect())
+    }
+
+    /// Return the decomposer of mentsute with the most mentsu and blocks.
+    /// Other ties are broken by the debug form, for a stable result.
+    fn block_decomposer(&self, player_number: PlayerNumber) -> Result<Decomposer, String> {
+        let (_, decomposers) = self.decompose(player_number)?;
+        decomposers
+            .into_iter()
+            .filter(|decomposer| decomposer.hourakei == Hourakei::Mentsute)
+            .max_by(|lhs, rhs| {
+                let key = |decomposer: &Decomposer| {
+                    (
+                        decomposer.mentsu_vec.len(),
+                        decomposer.toitsu_vec.len() + decomposer.taatsu_vec.len(),
+                        decomposer.toitsu_vec.len(),
+                    )
+                };
+                key(lhs)
+                    .cmp(&key(rhs))
+                    .then_with(|| format!("{:?}", rhs).cmp(&format!("{:?}", lhs)))
+            })
+            .ok_or("Logic error: No decomposer of mentsute.".to_string())
+    }
+
+    /// Return shanten of a 3*k+2 juntehai for each hourakei.
+    pub fn shanten_breakdown(
+        &self,
+        player_number: PlayerNumber,
+    ) -> Result<ShantenBreakdown, String> {
+        let decomposers = self.decompose_all(player_number)?;
+        let min_shanten = |hourakei| {
+            decomposers
+                .iter()
+                .filter(|decomposer| decomposer.hourakei == hourakei)
+                .map(|decomposer| decomposer.shanten(self.juntehai.len()))
+                .min()
+        };
+        let mentsute = min_shanten(Hourakei::Mentsute)
+            .ok_or("Logic error: No decomposer of mentsute.".to_string())?;
+        let chiitoitsu = min_shanten(Hourakei::Chiitoitsu);
+        let kokushimusou = min_shanten(Hourakei::Kokushimusou);
+        let min = mentsute
+            .min(chiitoitsu.unwrap_or(mentsute))
+            .min(kokushimusou.unwrap_or(mentsute));
+        Ok(ShantenBreakdown {
+            mentsute,
+            chiitoitsu,
+            kokushimusou,
+            min,
+        })
+    }
+
+    /// Build the ukeire tree of a 3*k+2 juntehai for depth draws, with the number of
+    /// machihai taken from haiyama. Depth is limited to `UKEIRE_TREE_MAX_DEPTH`.
+    pub fn ukeire_tree(
+        &self,
+        player_number: PlayerNumber,
+        haiyama: &Haiyama,
+        depth: u32,
+    ) -> Result<UkeireTree, String> {
+        if depth > UKEIRE_TREE_MAX_DEPTH {
+            return Err(format!(
+                "Depth of ukeire tree is limited to {}, but {} provided.",
+                UKEIRE_TREE_MAX_DEPTH, depth
+            ));
+        }
+
+        self.ukeire_tree_core(player_number, haiyama, depth, &mut HashMap::new())
+    }
+
+    /// Analyze a batch of tehai with `analyze_with_haiyama()` on multiple threads.
+    /// Results are in the same order as input, and the same as analyzing one by one.
+    ///
+    /// It is always available and uses `std::thread::scope()`, not behind a `rayon`
+    /// feature, so no dependency is added. Each result is that of `analyze_with_haiyama()`
+    /// instead of a dedicated evaluation type.
+    pub fn analyze_many(
+        tehai_slice: &[Tehai],
+        player_number: PlayerNumber,
+        haiyama: &Haiyama,
+    ) -> Vec<Result<(i32, Vec<MachiCondition>), String>> {
+        let threads = std::thread::available_parallelism().map_or(1, |number| number.get());
+        let chunk_size = std::cmp::max(1, tehai_slice.len().div_ceil(threads));
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = tehai_slice
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|tehai| tehai.analyze_with_haiyama(player_number, haiyama))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        })
+    }
+
+    fn analyze_core(
+        &self,
+        player_number: PlayerNumber,
+        haiyama: Option<&Haiyama>,
+        sutehai_type: &BTreeSet<Hai>,
+        tie_break: TieBreak,
+        excluded: &BTreeSet<Hai>,
+    ) -> Result<(i32, Vec<MachiCondition>), String> {
+        fn yaochuu_order(hai: &Hai) -> u8 {
+            match hai.number() {
+                None => 0,
+                Some(1) | Some(9) => 1,
+                Some(2) | Some(8) => 2,
+                Some(_) => 3,
+            }
+        }
+
         let (shanten, decomposers) = self.decompose(player_number)?;
         let mut conditions_vec = vec![];
 
@@ -270,29 +896,456 @@ impl Tehai {
                 }
             }
         }
+        sutehai_set.retain(|sutehai| !excluded.contains(sutehai));
         for sutehai in sutehai_set {
             let mut condition = MachiCondition::new(sutehai);
             for decomposer in &decomposers {
                 condition.handle(decomposer, self.juntehai.len(), player_number)?;
             }
-            condition.finally(self, game_manager);
+            condition.finally(self, haiyama, sutehai_type);
             conditions_vec.push(condition);
         }
 
         conditions_vec.retain(|conditon| conditon.nokori() > 0);
         conditions_vec.sort_by(|lhs, rhs| {
             if lhs.nokori().cmp(&rhs.nokori()) == std::cmp::Ordering::Equal {
-                lhs.sutehai.cmp(&rhs.sutehai)
+                match tie_break {
+                    TieBreak::HaiOrder => lhs.sutehai.cmp(&rhs.sutehai),
+                    TieBreak::YaochuupaiFirst => yaochuu_order(&lhs.sutehai)
+                        .cmp(&yaochuu_order(&rhs.sutehai))
+                        .then(lhs.sutehai.cmp(&rhs.sutehai)),
+                }
             } else {
                 lhs.nokori().cmp(&rhs.nokori()).reverse()
             }
         });
+        if let Some(best) = conditions_vec.first().map(|condition| condition.nokori()) {
+            for condition in &mut conditions_vec {
+                condition.loss = best - condition.nokori();
+            }
+        }
 
         Ok((shanten, conditions_vec))
     }
 
+    /// Return all hai which can make a 3*k+1 juntehai hourakei.
+    /// Hai whose all 4 are already on hand is not included.
+    ///
+    /// # Japanese
+    /// * machi: 待ち
+    pub fn machi(&self, player_number: PlayerNumber) -> Result<BTreeSet<Hai>, String> {
+        if self.juntehai.len() % 3 != 1 {
+            return Err(format!(
+                "The number of hai on hand must be 3*k+1, \
+                such as 7, 10, 13, but {} provided.",
+                self.juntehai.len()
+            ));
+        }
+
+        let mut machi = BTreeSet::new();
+        for hai in Hai::all_type(player_number) {
+            let mut tehai = self.clone();
+            tehai.juntehai.push(hai);
+            tehai.juntehai.sort();
+            if tehai.check_hai_number().is_err() {
+                continue;
+            }
+            if tehai.decompose(player_number)?.0 == -1 {
+                machi.insert(hai);
+            }
+        }
+        Ok(machi)
+    }
+
+    /// Return every machihai of a 3*k+1 juntehai with the hourakei tehai it completes.
+    pub fn completions(&self, player_number: PlayerNumber) -> Result<Vec<(Hai, Tehai)>, String> {
+        Ok(self
+            .machi(player_number)?
+            .into_iter()
+            .map(|hai| {
+                let mut tehai = self.clone();
+                tehai.juntehai.push(hai);
+                tehai.juntehai.sort();
+                (hai, tehai)
+            })
+            .collect())
+    }
+
+    /// Return every machihai of a 3*k+1 juntehai with the number of it in haiyama.
+    /// Haiyama should have own juntehai and seen hai removed.
+    pub fn winning_tile_live_count(
+        &self,
+        player_number: PlayerNumber,
+        haiyama: &Haiyama,
+    ) -> Result<Vec<(Hai, u8)>, String> {
+        Ok(self
+            .machi(player_number)?
+            .into_iter()
+            .map(|hai| (hai, haiyama[&hai]))
+            .collect())
+    }
+
+    /// Return all types of hai breaking tenpai of a 3*k+1 juntehai if kept, that is,
+    /// drawing the hai, tenpai is kept only by discarding it.
+    /// Machihai are not included.
+    pub fn tiles_that_reduce_tenpai(
+        &self,
+        player_number: PlayerNumber,
+    ) -> Result<Vec<Hai>, String> {
+        let machi = self.machi(player_number)?;
+        if machi.is_empty() {
+            return Err(format!("{} is not tenpai.", self));
+        }
+
+        let mut hai_vec = vec![];
+        for hai in Hai::all_type(player_number) {
+            if machi.contains(&hai) {
+                continue;
+            }
+            let mut tehai = self.clone();
+            tehai.juntehai.push(hai);
+            tehai.juntehai.sort();
+            if tehai.check_hai_number().is_err() {
+                continue;
+            }
+            let (shanten, conditions) = tehai.analyze(player_number, None)?;
+            let keeps_tenpai =
+                shanten == 0 && conditions.iter().any(|condition| condition.sutehai != hai);
+            if !keeps_tenpai {
+                hai_vec.push(hai);
+            }
+        }
+        Ok(hai_vec)
+    }
+
+    /// Classify every type of hai left in haiyama by the effect of drawing it to a 3*k+1
+    /// juntehai. Ukeire after drawing is counted with the drawn hai taken from haiyama.
+    pub fn improving_tiles(
+        &self,
+        player_number: PlayerNumber,
+        haiyama: &Haiyama,
+    ) -> Result<Vec<(Hai, ImprovementKind)>, String> {
+        let (shanten, ukeire) = self.shanten_and_ukeire(player_number, haiyama)?;
+        let ukeire: usize = ukeire.values().map(|number| *number as usize).sum();
+
+        let mut improving_tiles = vec![];
+        for hai in Hai::all_type(player_number) {
+            if !haiyama.can_draw(&hai) {
+                continue;
+            }
+            let mut tehai = self.clone();
+            tehai.juntehai.push(hai);
+            tehai.juntehai.sort();
+            let mut haiyama = haiyama.clone();
+            haiyama.discard(&hai)?;
+            let (added_shanten, conditions) =
+                tehai.analyze_with_haiyama(player_number, &haiyama)?;
+            let kind = if added_shanten < shanten {
+                ImprovementKind::ShantenAdvance
+            } else if conditions
+                .first()
+                .is_some_and(|condition| condition.nokori() > ukeire)
+            {
+                ImprovementKind::ShapeImprove
+            } else {
+                ImprovementKind::Useless
+            };
+            improving_tiles.push((hai, kind));
+        }
+        Ok(improving_tiles)
+    }
+
+    /// Analyze shanten and ukeire of a 3*k+1 juntehai before and after chii, pon or
+    /// daiminkan, with the number of hai taken from haiyama.
+    /// Daiminkan is analyzed before rinshanhai is added, with sutehai None.
+    pub fn call_analysis(
+        &self,
+        naku: &Naku,
+        player_number: PlayerNumber,
+        haiyama: &Haiyama,
+    ) -> Result<CallAnalysis, String> {
+        let (shanten, ukeire) = self.shanten_and_ukeire(player_number, haiyama)?;
+        let ukeire = ukeire.values().map(|number| *number as u32).sum();
+        let mut tehai = self.clone();
+        match naku {
+            Naku::Chii { juntsu, nakihai } => tehai.chii(juntsu, nakihai)?,
+            Naku::Pon(koutsu) => tehai.pon(koutsu)?,
+            Naku::Kan(Kan::Unknown {
+                kantsu,
+                rinshanhai: None,
+            })
+            | Naku::Kan(Kan::Daiminkan {
+                kantsu,
+                rinshanhai: None,
+            }) => {
+                if let Kan::Daiminkan { .. } = tehai.kan(kantsu, &None)? {
+                    let (naku_shanten, naku_ukeire) =
+                        tehai.shanten_and_ukeire(player_number, haiyama)?;
+                    return Ok(CallAnalysis {
+                        shanten,
+                        ukeire,
+                        naku_shanten,
+                        naku_ukeire: naku_ukeire.values().map(|number| *number as u32).sum(),
+                        sutehai: None,
+                    });
+                }
+                return Err("Logic error: Kan of 3*k+1 juntehai is not daiminkan.".to_string());
+            }
+            Naku::Kan(_) => {
+                return Err(
+                    "Only chii, pon and daiminkan without rinshanhai can be analyzed.".to_string(),
+                )
+            }
+        }
+        let (naku_shanten, conditions) = tehai.analyze_with_haiyama(player_number, haiyama)?;
+        let condition = conditions.first();
+        Ok(CallAnalysis {
+            shanten,
+            ukeire,
+            naku_shanten,
+            naku_ukeire: condition
+                .map(|condition| {
+                    condition
+                        .machihai
+                        .values()
+                        .map(|number| *number as u32)
+                        .sum()
+                })
+                .unwrap_or(0),
+            sutehai: condition.map(|condition| condition.sutehai),
+        })
+    }
+
+    /// Analyze every naku of a 3*k+1 juntehai on hai discarded by others, like
+    /// `call_analysis()`, in order of chii, pon and daiminkan.
+    /// Chii is only available on 4-players mode, and whose sutehai it is is not checked.
+    pub fn naku_options(
+        &self,
+        called_hai: &Hai,
+        player_number: PlayerNumber,
+        haiyama: &Haiyama,
+    ) -> Result<Vec<(Naku, CallAnalysis)>, String> {
+        let number = self
+            .juntehai
+            .iter()
+            .filter(|hai| *hai == called_hai)
+            .count();
+        let mut naku_vec = vec![];
+        if player_number == PlayerNumber::Four && called_hai.number().is_some() {
+            // Juntsu starting 2, 1 and 0 before called hai.
+            for back in (0..3).rev() {
+                let mut start = Some(*called_hai);
+                for _ in 0..back {
+                    start = start.and_then(|hai| hai.previous(player_number, false));
+                }
+                let middle = start.and_then(|hai| hai.next(player_number, false));
+                let last = middle.and_then(|hai| hai.next(player_number, false));
+                if let (Some(start), Some(middle), Some(last)) = (start, middle, last) {
+                    let mut tehai = self.clone();
+                    let juntsu = Mentsu::Juntsu(start, middle, last);
+                    if tehai.chii(&juntsu, called_hai).is_ok() {
+                        naku_vec.push(Naku::Chii {
+                            juntsu,
+                            nakihai: *called_hai,
+                        });
+                    }
+                }
+            }
+        }
+        if number >= 2 {
+            naku_vec.push(Naku::Pon(Mentsu::Koutsu(*called_hai)));
+        }
+        if number >= 3 {
+            naku_vec.push(Naku::Kan(Kan::Unknown {
+                kantsu: Mentsu::Kantsu(*called_hai),
+                rinshanhai: None,
+            }));
+        }
+
+        let mut options = vec![];
+        for naku in naku_vec {
+            let analysis = self.call_analysis(&naku, player_number, haiyama)?;
+            options.push((naku, analysis));
+        }
+        Ok(options)
+    }
+
+    /// Return true if ankan of hai changes machi, for a 3*k+2 juntehai with 4 of the hai.
+    /// Machi before kan is taken as the one after discarding the hai.
+    pub fn kan_changes_wait(&self, hai: &Hai, player_number: PlayerNumber) -> Result<bool, String> {
+        let mut before = self.clone();
+        before.discard(hai)?;
+        let mut after = self.clone();
+        if let Kan::Ankan { .. } = after.kan(&Mentsu::Kantsu(*hai), &None)? {
+            Ok(before.machi(player_number)? != after.machi(player_number)?)
+        } else {
+            Err(format!("Cannot ankan {}.", hai))
+        }
+    }
+
+    /// Compare efficiency of two 3*k+1 juntehai, by shanten and then the number of
+    /// hai reducing shanten in haiyama. Greater means self is more efficient.
+    pub fn efficiency_vs(
+        &self,
+        other: &Tehai,
+        player_number: PlayerNumber,
+        haiyama: &Haiyama,
+    ) -> Result<std::cmp::Ordering, String> {
+        let (shanten, ukeire) = self.shanten_and_ukeire(player_number, haiyama)?;
+        let (other_shanten, other_ukeire) = other.shanten_and_ukeire(player_number, haiyama)?;
+        let nokori: u32 = ukeire.values().map(|number| *number as u32).sum();
+        let other_nokori: u32 = other_ukeire.values().map(|number| *number as u32).sum();
+        Ok(other_shanten.cmp(&shanten).then(nokori.cmp(&other_nokori)))
+    }
+
+    /// Compare two taatsu or toitsu in juntehai to keep, by the number of hai completing
+    /// them to mentsu in haiyama, and then the number of types of the hai.
+    /// Greater means a is better to keep.
+    pub fn compare_taatsu(
+        &self,
+        a: (Hai, Hai),
+        b: (Hai, Hai),
+        player_number: PlayerNumber,
+        haiyama: &Haiyama,
+    ) -> Result<std::cmp::Ordering, String> {
+        let acceptance = |(lhs, rhs): (Hai, Hai)| -> Result<(u32, usize), String> {
+            let (lhs, rhs) = if lhs <= rhs { (lhs, rhs) } else { (rhs, lhs) };
+            let mut juntehai = self.juntehai.clone();
+            for hai in [lhs, rhs].iter() {
+                match juntehai.iter().position(|other| other == hai) {
+                    Some(index) => juntehai.remove(index),
+                    None => return Err(format!("{}{} is not in juntehai.", lhs, rhs)),
+                };
+            }
+
+            let next = lhs.next(player_number, false);
+            let hai_vec = if lhs == rhs {
+                vec![lhs]
+            } else if next == Some(rhs) {
+                vec![
+                    lhs.previous(player_number, false),
+                    rhs.next(player_number, false),
+                ]
+                .into_iter()
+                .flatten()
+                .collect()
+            } else if let Some(middle) =
+                next.filter(|middle| middle.next(player_number, false) == Some(rhs))
+            {
+                vec![middle]
+            } else {
+                return Err(format!("{}{} is not a taatsu or toitsu.", lhs, rhs));
+            };
+            Ok((
+                hai_vec.iter().map(|hai| haiyama[hai] as u32).sum(),
+                hai_vec.len(),
+            ))
+        };
+        Ok(acceptance(a)?.cmp(&acceptance(b)?))
+    }
+
+    /// Estimate the probability that a 3*k+1 juntehai becomes tenpai in the number of
+    /// draws, with hai drawn from haiyama without replacement.
+    ///
+    /// Every hai reducing shanten is kept, and the sutehai with the most machihai is
+    /// discarded, while other hai are discarded at once. Which hai not reducing shanten
+    /// are drawn is not tracked, so they are left in haiyama for later draws.
+    ///
+    /// Draws are limited to `TENPAI_PROBABILITY_MAX_DRAWS`. A tehai of more shanten than
+    /// draws gives 0 at once, but otherwise the cost grows quickly with shanten: it is
+    /// about a second for ryanshanten in all the draws, and far more for sanshanten.
+    pub fn tenpai_probability(
+        &self,
+        player_number: PlayerNumber,
+        haiyama: &Haiyama,
+        draws: u8,
+    ) -> Result<f64, String> {
+        if draws > TENPAI_PROBABILITY_MAX_DRAWS {
+            return Err(format!(
+                "Draws of tenpai probability are limited to {}, but {} provided.",
+                TENPAI_PROBABILITY_MAX_DRAWS, draws
+            ));
+        }
+        self.tenpai_probability_core(player_number, haiyama, draws, &mut HashMap::new())
+    }
+
+    /// Return the mentsu in juntehai completed by the winning hai, for a hourakei tehai
+    /// including the winning hai.
+    ///
+    /// If the winning hai can complete different mentsu, the one that gives fu of machi
+    /// is preferred -- kanchan and penchan first, then koutsu of shanpon, then ryanmen.
+    /// Return None if the tehai is not hourakei, or the winning hai can only complete a
+    /// toitsu (tanki).
+    ///
+    /// # Japanese
+    /// * kanchan: 嵌張
+    /// * penchan: 辺張
+    /// * ryanmen: 両面
+    /// * shanpon: 双碰
+    /// * tanki: 単騎
+    pub fn winning_group(&self, winning_hai: &Hai, player_number: PlayerNumber) -> Option<Mentsu> {
+        fn number(hai: &Hai) -> u8 {
+            match hai {
+                Hai::Manzu(num) | Hai::Pinzu(num) | Hai::Souzu(num) | Hai::Jihai(num) => *num,
+            }
+        }
+        fn priority(mentsu: &Mentsu, winning_hai: &Hai) -> u8 {
+            match mentsu {
+                Mentsu::Juntsu(a, b, c) => {
+                    if b == winning_hai
+                        || (c == winning_hai && number(a) == 1)
+                        || (a == winning_hai && number(c) == 9)
+                    {
+                        2
+                    } else {
+                        0
+                    }
+                }
+                _ => 1,
+            }
+        }
+
+        if !self.juntehai.contains(winning_hai) {
+            return None;
+        }
+        let (shanten, decomposers) = self.decompose(player_number).ok()?;
+        if shanten != -1 {
+            return None;
+        }
+
+        decomposers
+            .iter()
+            .filter(|decomposer| decomposer.hourakei == Hourakei::Mentsute)
+            .flat_map(|decomposer| decomposer.mentsu_vec.iter())
+            .filter(|mentsu| match mentsu {
+                Mentsu::Juntsu(a, b, c) => a == winning_hai || b == winning_hai || c == winning_hai,
+                Mentsu::Koutsu(hai) | Mentsu::Kantsu(hai) | Mentsu::Ankantsu(hai) => {
+                    hai == winning_hai
+                }
+            })
+            .max_by_key(|mentsu| priority(mentsu, winning_hai))
+            .copied()
+    }
+
+    /// Return true if no mentsu in fuuro is formed by naku. Ankan does not break it.
+    ///
+    /// # Japanese
+    /// * menzen: 門前
+    pub fn is_closed(&self) -> bool {
+        self.fuuro
+            .iter()
+            .all(|mentsu| matches!(mentsu, Mentsu::Ankantsu(_)))
+    }
+
+    /// Return the number of hai on hand, regarding every mentsu in fuuro as 3 hai.
+    /// It is 13 or 14 for a normal tehai.
+    pub fn tile_total(&self) -> u8 {
+        (self.juntehai.len() + 3 * self.fuuro.len()) as u8
+    }
+
     /// Discard a hai from juntehai.
     pub fn discard(&mut self, hai: &Hai) -> Result<(), String> {
+        let tile_total = self.tile_total();
         let mut index = None;
         for (i, item) in self.juntehai.iter().enumerate() {
             if item == hai {
@@ -303,6 +1356,7 @@ impl Tehai {
         match index {
             Some(index) => {
                 self.juntehai.remove(index);
+                debug_assert_eq!(self.tile_total(), tile_total - 1);
                 Ok(())
             }
             None => return Err(format!("No enough {} to discard.", hai.to_string())),
@@ -312,6 +1366,18 @@ impl Tehai {
     /// Chii, for an example, 23m catch 4m.
     pub fn chii(&mut self, juntsu: &Mentsu, nakihai: &Hai) -> Result<(), String> {
         if let Mentsu::Juntsu(a, b, c) = juntsu {
+            let mut numbers = [a.number(), b.number(), c.number()];
+            numbers.sort();
+            let consecutive = match numbers {
+                [Some(x), Some(y), Some(z)] => x + 1 == y && y + 1 == z,
+                _ => false,
+            };
+            if !consecutive || a.suit() != b.suit() || a.suit() != c.suit() {
+                return Err(format!("{} is not a valid juntsu.", juntsu));
+            }
+            if nakihai != a && nakihai != b && nakihai != c {
+                return Err(format!("{} is not in {}.", nakihai, juntsu));
+            }
             let backup = self.clone();
             for hai in vec![a, b, c] {
                 if hai == nakihai {
@@ -323,6 +1389,7 @@ impl Tehai {
                 }
             }
             self.fuuro.push(*juntsu);
+            debug_assert_eq!(self.tile_total(), backup.tile_total() + 1);
             Ok(())
         } else {
             Err("Logic error: Tehai::chii() can only accept Mentsu::Juntsu.".to_string())
@@ -340,6 +1407,7 @@ impl Tehai {
                 }
             }
             self.fuuro.push(*koutsu);
+            debug_assert_eq!(self.tile_total(), backup.tile_total() + 1);
             Ok(())
         } else {
             Err("Logic error: Tehai::pon() can only accept Mentsu::Koutsu.".to_string())
@@ -349,6 +1417,7 @@ impl Tehai {
     /// Kan, for an example, 222m catch 2m.
     pub fn kan(&mut self, kantsu: &Mentsu, rinshanhai: &Option<Hai>) -> Result<Kan, String> {
         if let Mentsu::Kantsu(hai) = kantsu {
+            let tile_total = self.tile_total();
             let mut hai_num = 0;
             let mut exist_koutsu = false;
             let mut exist_koutsu_index = 0;
@@ -358,7 +1427,7 @@ impl Tehai {
                 }
             }
             for (index, mentsu) in self.fuuro.iter().enumerate() {
-                if let Mentsu::Kantsu(i) = mentsu {
+                if let Mentsu::Koutsu(i) = mentsu {
                     if i == hai {
                         exist_koutsu = true;
                         exist_koutsu_index = index;
@@ -382,7 +1451,7 @@ impl Tehai {
                         // Undoubtedly exist. Ignore the error.
                         self.discard(hai)?;
                     }
-                    self.fuuro.push(kantsu.clone());
+                    self.fuuro.push(Mentsu::Ankantsu(*hai));
                     kan = Kan::Ankan {
                         kantsu: *kantsu,
                         rinshanhai: rinshanhai.clone(),
@@ -415,6 +1484,13 @@ impl Tehai {
                 self.juntehai.push(*rinshanhai);
                 self.juntehai.sort();
             }
+            debug_assert_eq!(
+                self.tile_total(),
+                match kan {
+                    Kan::Daiminkan { .. } => tile_total,
+                    _ => tile_total - 1,
+                } + rinshanhai.is_some() as u8
+            );
             Ok(kan)
         } else {
             Err("Logic error: Tehai::kan() can only accept Mentsu::Kantsu.".to_string())
@@ -502,7 +1578,7 @@ impl Tehai {
             }
             Kan::Ankan { kantsu, rinshanhai } => {
                 if let Mentsu::Kantsu(hai) = kantsu {
-                    discard_kantsu(&mut self.fuuro, kantsu)?;
+                    discard_kantsu(&mut self.fuuro, &Mentsu::Ankantsu(*hai))?;
                     for _ in 0..4 {
                         self.juntehai.push(*hai);
                     }
@@ -515,8 +1591,13 @@ impl Tehai {
             }
             Kan::Kakan { kantsu, rinshanhai } => {
                 if let Mentsu::Kantsu(hai) = kantsu {
-                    discard_kantsu(&mut self.fuuro, kantsu)?;
-                    self.fuuro.push(Mentsu::Koutsu(*hai));
+                    // Put koutsu back in place of kantsu, as kakan did.
+                    let mentsu = self
+                        .fuuro
+                        .iter_mut()
+                        .find(|mentsu| *mentsu == kantsu)
+                        .ok_or("Logic error: can not find kantsu in fuuro.".to_string())?;
+                    *mentsu = Mentsu::Koutsu(*hai);
                     self.juntehai.push(*hai);
                 } else {
                     return Err(
@@ -529,6 +1610,7 @@ impl Tehai {
                 return Err("Logic error: Tehai::de_kan() can not accept Kan::Unknown.".to_string())
             }
         };
+        self.juntehai.sort();
         if let Some(rinshanhai) = rinshanhai {
             if let Err(error) = self.discard(rinshanhai) {
                 *self = backup;
@@ -554,25 +1636,200 @@ impl Tehai {
         })
     }
 
-    /// Decompose self to a vec of Decomposer.
+    fn ukeire_tree_core(
+        &self,
+        player_number: PlayerNumber,
+        haiyama: &Haiyama,
+        depth: u32,
+        memo: &mut HashMap<(Vec<Hai>, Vec<u8>, u32), UkeireTree>,
+    ) -> Result<UkeireTree, String> {
+        let all_type = Hai::all_type(player_number);
+        let key = (
+            self.juntehai.clone(),
+            all_type.iter().map(|hai| haiyama[hai]).collect(),
+            depth,
+        );
+        if let Some(tree) = memo.get(&key) {
+            return Ok(tree.clone());
+        }
+
+        let (shanten, conditions) = self.analyze_with_haiyama(player_number, haiyama)?;
+        let mut children = BTreeMap::new();
+        if depth == 0 {
+            return Ok(UkeireTree { shanten, children });
+        }
+        for condition in &conditions {
+            let mut discarded = self.clone();
+            discarded.discard(&condition.sutehai)?;
+            for (machihai, number) in &condition.machihai {
+                // Every machihai reduces shanten by one, so leaves need no analysis.
+                let child = if depth == 1 {
+                    UkeireTree {
+                        shanten: shanten - 1,
+                        children: BTreeMap::new(),
+                    }
+                } else {
+                    let mut tehai = discarded.clone();
+                    tehai.juntehai.push(*machihai);
+                    tehai.juntehai.sort();
+                    let mut rest = haiyama.clone();
+                    rest.discard(machihai)?;
+                    tehai.ukeire_tree_core(player_number, &rest, depth - 1, memo)?
+                };
+                children.insert((condition.sutehai, *machihai), (*number, child));
+            }
+        }
+
+        let tree = UkeireTree { shanten, children };
+        memo.insert(key, tree.clone());
+        Ok(tree)
+    }
+
+    fn tenpai_probability_core(
+        &self,
+        player_number: PlayerNumber,
+        haiyama: &Haiyama,
+        draws: u8,
+        memo: &mut HashMap<(Vec<Hai>, Vec<u8>, u8), f64>,
+    ) -> Result<f64, String> {
+        let (shanten, ukeire) = self.shanten_and_ukeire(player_number, haiyama)?;
+        if shanten <= 0 {
+            return Ok(1.0);
+        }
+        // Each draw reduces shanten by 1 at most.
+        if (draws as i32) < shanten {
+            return Ok(0.0);
+        }
+        let all_type = Hai::all_type(player_number);
+        let key = (
+            self.juntehai.clone(),
+            all_type.iter().map(|hai| haiyama[hai]).collect(),
+            draws,
+        );
+        if let Some(probability) = memo.get(&key) {
+            return Ok(*probability);
+        }
+
+        let total = haiyama.remaining() as f64;
+        let useful: f64 = ukeire.values().map(|number| *number as f64).sum();
+        let mut probability = 0.0;
+        for (hai, number) in &ukeire {
+            if *number == 0 {
+                continue;
+            }
+            let mut tehai = self.clone();
+            tehai.juntehai.push(*hai);
+            tehai.juntehai.sort();
+            let mut rest = haiyama.clone();
+            rest.discard(hai)?;
+            let (_, conditions) = tehai.analyze_with_haiyama(player_number, &rest)?;
+            match conditions.first() {
+                Some(condition) => tehai.discard(&condition.sutehai)?,
+                None => continue,
+            }
+
+            // Draw the hai after missed times. Tehai is then of shanten - 1, so it is
+            // tenpai at once from iishanten, and is skipped when too few draws are left.
+            let mut missed_probability = 1.0;
+            for missed in 0..=(draws as i32 - shanten) as u8 {
+                let left = total - missed as f64;
+                if left <= 0.0 {
+                    break;
+                }
+                let child_probability = if shanten == 1 {
+                    1.0
+                } else {
+                    tehai.tenpai_probability_core(player_number, &rest, draws - 1 - missed, memo)?
+                };
+                probability += missed_probability * *number as f64 / left * child_probability;
+                missed_probability *= (left - useful).max(0.0) / left;
+            }
+        }
+
+        memo.insert(key, probability);
+        Ok(probability)
+    }
+
+    /// Return shanten of juntehai, which never depends on haiyama.
+    /// For a 3*k+2 juntehai it is the same as `analyze()`, and for a 3*k+1 juntehai it is
+    /// the same as `shanten_and_ukeire()`.
+    pub fn shanten(&self, player_number: PlayerNumber) -> Result<i32, String> {
+        if self.juntehai.len() % 3 == 2 {
+            Ok(self.decompose(player_number)?.0)
+        } else {
+            Ok(self.added_shanten(player_number)?.0)
+        }
+    }
+
+    /// Return shanten of a 3*k+1 juntehai, and all types of hai reducing shanten with
+    /// the number of them in haiyama. Only ukeire depends on haiyama.
     ///
-    /// # Return
-    /// * The `i32` data is the minimum shanten.
-    /// * The `HashSet<Decomposer>` data is all decomposers that thier shanten are minimum one.
-    fn decompose(&self, player_number: PlayerNumber) -> Result<(i32, HashSet<Decomposer>), String> {
-        // Only work for 3*k+2 juntehai.
-        if self.juntehai.len() % 3 != 2 {
+    /// Juntehai can be of any size, such as 10 hai with 1 fuuro, or a 7 hai fragment
+    /// for study. The number of mentsu to make is (juntehai - 1) / 3, and fuuro are not
+    /// counted, so 0 means tenpai.
+    pub fn shanten_and_ukeire(
+        &self,
+        player_number: PlayerNumber,
+        haiyama: &Haiyama,
+    ) -> Result<(i32, BTreeMap<Hai, u8>), String> {
+        let (shanten, shanten_vec) = self.added_shanten(player_number)?;
+        let ukeire = shanten_vec
+            .iter()
+            .filter(|(_, added_shanten)| *added_shanten < shanten)
+            .map(|(hai, _)| (*hai, haiyama[hai]))
+            .collect();
+        Ok((shanten, ukeire))
+    }
+
+    /// Return true if no hai reducing shanten of a 3*k+1 juntehai is left in haiyama.
+    pub fn is_dead_shape(
+        &self,
+        player_number: PlayerNumber,
+        haiyama: &Haiyama,
+    ) -> Result<bool, String> {
+        let (_, ukeire) = self.shanten_and_ukeire(player_number, haiyama)?;
+        Ok(ukeire.values().all(|number| *number == 0))
+    }
+
+    /// Return shanten of a 3*k+1 juntehai, and shanten after adding each type of hai.
+    fn added_shanten(&self, player_number: PlayerNumber) -> Result<(i32, Vec<(Hai, i32)>), String> {
+        if self.juntehai.len() % 3 != 1 {
             return Err(format!(
-                "The number of hai on hand must be 3*k+2, \
-                such as 8, 11, 14, even 17, but {} provided.",
+                "The number of hai on hand must be 3*k+1, \
+                such as 7, 10, 13, but {} provided.",
                 self.juntehai.len()
             ));
         }
 
+        // Adding a hai reduces shanten by 1 at most, and never increases it.
+        let mut shanten_vec = vec![];
+        for hai in Hai::all_type(player_number) {
+            let mut tehai = self.clone();
+            tehai.juntehai.push(hai);
+            tehai.juntehai.sort();
+            if tehai.check_hai_number().is_err() {
+                continue;
+            }
+            shanten_vec.push((hai, tehai.decompose(player_number)?.0));
+        }
+        let shanten = shanten_vec
+            .iter()
+            .map(|(_, shanten)| *shanten)
+            .max()
+            .ok_or("Logic error: No hai can be added.".to_string())?;
+        Ok((shanten, shanten_vec))
+    }
+
+    /// Decompose self to a vec of Decomposer.
+    ///
+    /// # Return
+    /// * The `i32` data is the minimum shanten.
+    /// * The `HashSet<Decomposer>` data is all decomposers that thier shanten are minimum one.
+    fn decompose(&self, player_number: PlayerNumber) -> Result<(i32, HashSet<Decomposer>), String> {
         let mut min_shanten = ((self.juntehai.len() / 3) * 2) as i32;
         let mut min_shanten_decomposers = HashSet::new();
 
-        let mut push_into_decomposers = |decomposer: Decomposer| {
+        for decomposer in self.decompose_all(player_number)? {
             if decomposer.shanten(self.juntehai.len()) == min_shanten {
                 min_shanten_decomposers.insert(decomposer);
             } else if decomposer.shanten(self.juntehai.len()) < min_shanten {
@@ -580,19 +1837,32 @@ impl Tehai {
                 min_shanten_decomposers.clear();
                 min_shanten_decomposers.insert(decomposer);
             }
-        };
+        }
+
+        Ok((min_shanten, min_shanten_decomposers))
+    }
+
+    /// Decompose self to all decomposers of every hourakei, a part of function of decompose.
+    fn decompose_all(&self, player_number: PlayerNumber) -> Result<Vec<Decomposer>, String> {
+        // Only work for 3*k+2 juntehai.
+        if self.juntehai.len() % 3 != 2 {
+            return Err(format!(
+                "The number of hai on hand must be 3*k+2, \
+                such as 8, 11, 14, even 17, but {} provided.",
+                self.juntehai.len()
+            ));
+        }
 
         // Analyze Mentsute
         let mut decomposers_vec = vec![];
         self.split(&mut decomposers_vec, &mut Decomposer::new(), player_number);
-        for mut decomposer in decomposers_vec {
+        for decomposer in decomposers_vec.iter_mut() {
             decomposer.hourakei = Hourakei::Mentsute;
-            push_into_decomposers(decomposer);
         }
 
         // Analyze Chiitoitsu and Kokushimusou.
         if self.juntehai.len() != 14 || self.fuuro.len() != 0 {
-            return Ok((min_shanten, min_shanten_decomposers));
+            return Ok(decomposers_vec);
         }
 
         // Analyze Chiitoitsu
@@ -627,7 +1897,7 @@ impl Tehai {
                 }
             }
 
-            push_into_decomposers(decomposer);
+            decomposers_vec.push(decomposer);
         }
 
         // Analyze Kokushimusou
@@ -664,9 +1934,9 @@ impl Tehai {
                 }
             }
         }
-        push_into_decomposers(decomposer);
+        decomposers_vec.push(decomposer);
 
-        Ok((min_shanten, min_shanten_decomposers))
+        Ok(decomposers_vec)
     }
 
     fn check_hai_number(&self) -> Result<(), Hai> {
@@ -706,7 +1976,7 @@ impl Tehai {
                         }
                     }
                 }
-                Mentsu::Kantsu(hai) => {
+                Mentsu::Kantsu(hai) | Mentsu::Ankantsu(hai) => {
                     for _ in 0..4 {
                         if !check_count(hai) {
                             return Err(*hai);
@@ -977,6 +2247,13 @@ impl Decomposer {
     }
 }
 
+impl BlockReport {
+    /// Return the number of blocks more than target, negative if less.
+    pub fn overflow(&self) -> i32 {
+        (self.mentsu + self.toitsu + self.taatsu) as i32 - self.target as i32
+    }
+}
+
 impl MachiCondition {
     /// Get how many hai can waiting for.
     ///
@@ -1002,6 +2279,7 @@ impl MachiCondition {
             "sutehai": self.sutehai.to_string(),
             "furiten": self.furiten,
             "machihai_number": self.nokori(),
+            "loss": self.loss,
             "machihai": machi_hai_json_vec
         })
     }
@@ -1011,6 +2289,7 @@ impl MachiCondition {
             sutehai,
             machihai: BTreeMap::new(),
             furiten: false,
+            loss: 0,
         }
     }
 
@@ -1107,17 +2386,17 @@ impl MachiCondition {
         Ok(self)
     }
 
-    fn finally(&mut self, tehai: &Tehai, game_manager: Option<&GameManager>) {
+    fn finally(&mut self, tehai: &Tehai, haiyama: Option<&Haiyama>, sutehai_type: &BTreeSet<Hai>) {
         // If interactive mode.
-        if let Some(game_manager) = game_manager {
+        if let Some(haiyama) = haiyama {
             let mut zero_nokori_hai = vec![];
             for (key, value) in self.machihai.iter_mut() {
                 if !self.furiten {
-                    if game_manager.sutehai_type().contains(key) {
+                    if sutehai_type.contains(key) {
                         self.furiten = true;
                     }
                 }
-                *value = game_manager.haiyama()[key];
+                *value = haiyama[key];
                 if *value == 0 {
                     zero_nokori_hai.push(*key);
                 }
@@ -1154,7 +2433,7 @@ impl MachiCondition {
                             check_count(&mut self.machihai, item);
                         }
                     }
-                    Mentsu::Kantsu(item) => {
+                    Mentsu::Kantsu(item) | Mentsu::Ankantsu(item) => {
                         for _ in 0..4 {
                             check_count(&mut self.machihai, item);
                         }
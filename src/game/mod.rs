@@ -1,9 +1,17 @@
+pub mod defense;
 mod game_manager;
 mod mahjong;
+pub mod paifu;
+pub mod score;
+mod table;
 
 pub use game_manager::{
-    GameManager, HaiyamaOperation, Kan, Naku, Operation, State, TehaiOperation,
+    DiscardProfile, FuritenKind, GameManager, HaiyamaOperation, Kan, Naku, Operation,
+    OperationKind, State, TehaiOperation, EARLY_DISCARD_NUMBER, JSON_VERSION,
 };
 pub use mahjong::{
-    Hai, Haiyama, MachiCondition, Mentsu, PlayerNumber, Taatsu, Tehai, Toitsu, Ukihai,
+    BlockReport, CallAnalysis, Hai, Haiyama, HandArchetype, ImprovementKind, MachiCondition,
+    Mentsu, PlayerNumber, ShantenBreakdown, Suit, Taatsu, Tehai, TieBreak, Toitsu, UkeireTree,
+    Ukihai, TENPAI_PROBABILITY_MAX_DRAWS, UKEIRE_TREE_MAX_DEPTH,
 };
+pub use table::{Table, DEFAULT_WANPAI_SIZE, MAX_RINSHANHAI_NUMBER};
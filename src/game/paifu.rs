@@ -0,0 +1,35 @@
+use super::{GameManager, Operation, PlayerNumber};
+
+/// Parse a round log of one player to operations to replay in order.
+///
+/// A round log has one command of `GameManager::parse_command()` on each line,
+/// from `init` of the haipai. Blank lines are ignored, and so is everything after `#`.
+///
+/// ```text
+/// # East 1
+/// init 123m456p789s1123z
+/// draw 4z
+/// discard 4z
+/// seen 56z
+/// ```
+///
+/// # Japanese
+/// * paifu: 牌譜
+/// * haipai: 配牌
+pub fn parse_round(log: &str, player_number: PlayerNumber) -> Result<Vec<Operation>, String> {
+    let mut operations = vec![];
+    for (index, line) in log.lines().enumerate() {
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        operations.push(
+            GameManager::parse_command(line, player_number)
+                .map_err(|error| format!("Line {}: {}", index + 1, error))?,
+        );
+    }
+    Ok(operations)
+}
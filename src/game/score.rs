@@ -0,0 +1,85 @@
+/// Total points paid by noten players to tenpai players at ryuukyoku.
+pub const NOTEN_BAPPU: i32 = 3000;
+
+/// Points paid to the winner for each honba.
+pub const HONBA_POINTS: i32 = 300;
+
+/// Points of each riichi stick on the table, taken by the winner.
+pub const RIICHI_STICK_POINTS: i32 = 1000;
+
+/// Return the change of points of each seat at ryuukyoku of 4-players mode, by whether
+/// each seat is tenpai. Noten players pay `NOTEN_BAPPU` points in total, split evenly,
+/// and tenpai players share them. Nothing is paid if all or none are tenpai.
+///
+/// # Japanese
+/// * ryuukyoku: 流局
+/// * noten bappu: 不聴罰符
+pub fn ryuukyoku_payments(tenpai: &[bool; 4]) -> [i32; 4] {
+    let tenpai_number = tenpai.iter().filter(|tenpai| **tenpai).count() as i32;
+    let mut payments = [0; 4];
+    if tenpai_number == 0 || tenpai_number == 4 {
+        return payments;
+    }
+    for (payment, tenpai) in payments.iter_mut().zip(tenpai) {
+        *payment = if *tenpai {
+            NOTEN_BAPPU / tenpai_number
+        } else {
+            -NOTEN_BAPPU / (4 - tenpai_number)
+        };
+    }
+    payments
+}
+
+/// Return the change of points of each seat when winners ron the same sutehai of
+/// discarder in 4-players mode, with the points each winner is paid.
+/// If atamahane, only the first winner counterclockwise from discarder is paid.
+/// Otherwise discarder pays every winner.
+/// The first winner also takes `HONBA_POINTS` for each honba from discarder, and
+/// `RIICHI_STICK_POINTS` for each riichi stick on the table, which nobody pays.
+///
+/// # Japanese
+/// * ron: 栄和
+/// * atamahane: 頭跳ね
+/// * honba: 本場
+/// * riichi stick: 供託
+pub fn ron_payments(
+    discarder: usize,
+    winners: &[(usize, i32)],
+    atamahane: bool,
+    honba: u8,
+    riichi_sticks: u8,
+) -> Result<[i32; 4], String> {
+    if discarder >= 4 {
+        return Err(format!("No seat {}.", discarder));
+    }
+    if winners.is_empty() {
+        return Err("No winner.".to_string());
+    }
+    for (i, (seat, _)) in winners.iter().enumerate() {
+        if *seat >= 4 {
+            return Err(format!("No seat {}.", seat));
+        }
+        if *seat == discarder {
+            return Err(format!("Seat {} cannot ron its own sutehai.", seat));
+        }
+        if winners[..i].iter().any(|(other, _)| other == seat) {
+            return Err(format!("Seat {} wins more than once.", seat));
+        }
+    }
+
+    let mut winners = winners.to_vec();
+    // Order by distance counterclockwise from discarder.
+    winners.sort_by_key(|(seat, _)| (seat + 4 - discarder) % 4);
+    if atamahane {
+        winners.truncate(1);
+    }
+    let mut payments = [0; 4];
+    let first = winners[0].0;
+    payments[first] += RIICHI_STICK_POINTS * riichi_sticks as i32;
+    winners[0].1 += HONBA_POINTS * honba as i32;
+    for (seat, points) in winners {
+        payments[seat] += points;
+        payments[discarder] -= points;
+    }
+    Ok(payments)
+}
@@ -0,0 +1,238 @@
+use super::{Hai, Haiyama, PlayerNumber, Tehai};
+use std::collections::VecDeque;
+
+/// Default number of hai in wanpai.
+pub const DEFAULT_WANPAI_SIZE: usize = 14;
+
+/// Maximum number of rinshanhai in wanpai, which is also the maximum number of kan.
+pub const MAX_RINSHANHAI_NUMBER: usize = 4;
+
+/// A table of mahjong, holding tehai of every seat and the shared haiyama.
+///
+/// Haiyama of table includes all hai not dealt or drawn by any seat yet.
+/// The last hai of wall are wanpai, which are never drawn by `draw()`.
+/// As dealt, the last 4 hai of wanpai are rinshanhai, drawn from the end by
+/// `draw_rinshan()`, and the 5th, 7th, 9th, 11th and 13th hai from the end are
+/// dora indicators.
+/// Wanpai keeps its size after a rinshanhai is drawn, by taking the last hai of the
+/// live wall.
+/// Seat 0 is oya. The game ends by ryuukyoku when a seat draws from an empty wall.
+///
+/// # Japanese
+/// * oya: 親
+/// * wanpai: 王牌
+/// * rinshanhai: 嶺上牌
+/// * dora indicator: ドラ表示牌
+/// * ryuukyoku: 流局
+#[derive(Clone, Debug)]
+pub struct Table {
+    haiyama: Haiyama,
+    wall: VecDeque<Hai>,
+    wanpai_size: usize,
+    tehai_vec: Vec<Tehai>,
+    sutehai_vec: Vec<Vec<Hai>>,
+    player_number: PlayerNumber,
+    exhausted: bool,
+    rinshanhai_drawn: usize,
+}
+
+impl Table {
+    /// Deal 13 hai to every seat from the wall, 4 hai to each seat in turns for 3 times
+    /// and then 1 hai, the rest of wall is drawn in order by `draw()`.
+    /// Wanpai is `DEFAULT_WANPAI_SIZE` hai.
+    pub fn deal(wall: &[Hai], player_number: PlayerNumber) -> Result<Self, String> {
+        Self::deal_with_wanpai(wall, player_number, DEFAULT_WANPAI_SIZE)
+    }
+
+    /// Deal like `deal()`, with wanpai_size hai of wanpai.
+    pub fn deal_with_wanpai(
+        wall: &[Hai],
+        player_number: PlayerNumber,
+        wanpai_size: usize,
+    ) -> Result<Self, String> {
+        let seat_number = match player_number {
+            PlayerNumber::Three => 3,
+            PlayerNumber::Four => 4,
+        };
+        if wall.len() < seat_number * 13 + wanpai_size {
+            return Err(format!(
+                "Cannot deal {} hai to {} seats with {} wanpai.",
+                wall.len(),
+                seat_number,
+                wanpai_size
+            ));
+        }
+
+        if let Some(hai) = wall.iter().find(|hai| !hai.is_valid(player_number)) {
+            return Err(format!("'{}' is invalid hai.", hai));
+        }
+
+        let mut haiyama = Haiyama::new(player_number);
+        // Check no more than 4 of each hai in wall, then put back the hai not dealt.
+        haiyama.discard_with_vec(&wall.to_vec(), true)?;
+        haiyama.add_with_vec(&wall[seat_number * 13..].to_vec(), true)?;
+
+        let mut wall: VecDeque<Hai> = wall.iter().copied().collect();
+        let mut juntehai_vec = vec![vec![]; seat_number];
+        for number in [4, 4, 4, 1] {
+            for juntehai in juntehai_vec.iter_mut() {
+                juntehai.extend(wall.drain(..number));
+            }
+        }
+        let tehai_vec = juntehai_vec
+            .into_iter()
+            .map(|mut juntehai| {
+                juntehai.sort();
+                Tehai {
+                    juntehai,
+                    fuuro: vec![],
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            haiyama,
+            wall,
+            wanpai_size,
+            tehai_vec,
+            sutehai_vec: vec![vec![]; seat_number],
+            player_number,
+            exhausted: false,
+            rinshanhai_drawn: 0,
+        })
+    }
+
+    /// Return the number of players.
+    pub fn player_number(&self) -> PlayerNumber {
+        self.player_number
+    }
+
+    /// Return a reference of haiyama.
+    pub fn haiyama(&self) -> &Haiyama {
+        &self.haiyama
+    }
+
+    /// Return the number of hai left in wall.
+    pub fn wall_remaining(&self) -> usize {
+        self.wall.len()
+    }
+
+    /// Return the number of hai left in wall except wanpai.
+    pub fn live_wall_remaining(&self) -> usize {
+        self.wall.len() - self.wanpai_size
+    }
+
+    /// Return the number of hai left in wanpai, which never changes.
+    pub fn wanpai_remaining(&self) -> usize {
+        self.wanpai_size
+    }
+
+    /// Return the number of rinshanhai left in wanpai.
+    pub fn rinshanhai_remaining(&self) -> usize {
+        MAX_RINSHANHAI_NUMBER.min(self.wanpai_size) - self.rinshanhai_drawn
+    }
+
+    /// Return revealed dora indicators in order, one at first and one more after each
+    /// rinshanhai drawn. Dora indicators not fitting in wanpai are omitted.
+    pub fn dora_indicators(&self) -> Vec<Hai> {
+        (0..=self.rinshanhai_drawn)
+            // Position from the end of wanpai as dealt.
+            .map(|i| MAX_RINSHANHAI_NUMBER + 2 * i)
+            .filter(|position| *position < self.wanpai_size)
+            .map(|position| self.wall[self.wall.len() - 1 - (position - self.rinshanhai_drawn)])
+            .collect()
+    }
+
+    /// Return dora indicators revealed by kan in order, which are all of
+    /// `dora_indicators()` but the first one. They count as dora the same as the first.
+    ///
+    /// # Japanese
+    /// * kan-dora: カンドラ
+    pub fn kan_dora_indicators(&self) -> Vec<Hai> {
+        self.dora_indicators().into_iter().skip(1).collect()
+    }
+
+    /// Return the number of rounds that every seat can still draw.
+    pub fn turns_remaining(&self) -> usize {
+        self.live_wall_remaining() / self.tehai_vec.len()
+    }
+
+    /// Return true if the last hai except wanpai is drawn, which means the hai just drawn
+    /// is haitei, or the next sutehai is houtei. Hai in wanpai are never counted.
+    ///
+    /// # Japanese
+    /// * haitei: 海底
+    /// * houtei: 河底
+    pub fn is_last_tile(&self) -> bool {
+        self.live_wall_remaining() == 0
+    }
+
+    /// Return the reference of tehai of seat.
+    pub fn tehai(&self, seat: usize) -> Option<&Tehai> {
+        self.tehai_vec.get(seat)
+    }
+
+    /// Return all sutehai of seat in the order they are discarded.
+    pub fn sutehai(&self, seat: usize) -> Option<&Vec<Hai>> {
+        self.sutehai_vec.get(seat)
+    }
+
+    /// Return true if the game ended by ryuukyoku, a seat drawing from an empty wall.
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted
+    }
+
+    /// Draw next hai of wall to tehai of seat and return it.
+    /// Return `None` and end the game by ryuukyoku if there is no hai in wall except wanpai.
+    pub fn draw(&mut self, seat: usize) -> Result<Option<Hai>, String> {
+        if seat >= self.tehai_vec.len() {
+            return Err(format!("No seat {}.", seat));
+        }
+        if self.live_wall_remaining() == 0 {
+            self.exhausted = true;
+            return Ok(None);
+        }
+        // Wall is never shorter than wanpai.
+        let hai = self.wall.pop_front().unwrap();
+        self.haiyama.discard(&hai)?;
+        let tehai = &mut self.tehai_vec[seat];
+        tehai.juntehai.push(hai);
+        tehai.juntehai.sort();
+        Ok(Some(hai))
+    }
+
+    /// Draw the last hai of wanpai to tehai of seat as rinshanhai and return it.
+    /// The last hai of the live wall becomes a part of wanpai, so there is one less hai
+    /// to draw by `draw()`. Return error if no rinshanhai is left, or the live wall is
+    /// empty, when kan is not allowed.
+    pub fn draw_rinshan(&mut self, seat: usize) -> Result<Hai, String> {
+        if seat >= self.tehai_vec.len() {
+            return Err(format!("No seat {}.", seat));
+        }
+        if self.rinshanhai_remaining() == 0 {
+            return Err("No more rinshanhai in wanpai.".to_string());
+        }
+        if self.live_wall_remaining() == 0 {
+            return Err("No more hai in wall to refill wanpai.".to_string());
+        }
+        // Wall is never shorter than wanpai, and wanpai is never empty here.
+        let hai = self.wall.pop_back().unwrap();
+        self.rinshanhai_drawn += 1;
+        self.haiyama.discard(&hai)?;
+        let tehai = &mut self.tehai_vec[seat];
+        tehai.juntehai.push(hai);
+        tehai.juntehai.sort();
+        Ok(hai)
+    }
+
+    /// Discard hai from tehai of seat.
+    pub fn discard(&mut self, seat: usize, hai: &Hai) -> Result<(), String> {
+        let tehai = self
+            .tehai_vec
+            .get_mut(seat)
+            .ok_or(format!("No seat {}.", seat))?;
+        tehai.discard(hai)?;
+        self.sutehai_vec[seat].push(*hai);
+        Ok(())
+    }
+}
@@ -82,7 +82,10 @@ impl Command {
                 let hai_vec = game::Hai::from_string_unordered(&command, player_number)?;
                 if hai_vec.len() == 1 {
                     Ok(Command::GameOperation(game::Operation::Tehai(
-                        game::TehaiOperation::Discard(hai_vec[0]),
+                        game::TehaiOperation::Discard {
+                            hai: hai_vec[0],
+                            tsumogiri: false,
+                        },
                     )))
                 } else {
                     Err("Can only discard one hai when use '-' operator.".to_string())
@@ -1,8 +1,7 @@
 #![forbid(unsafe_code)]
 
-mod game;
-mod interaction;
 use clap::Parser;
+use japanese_mahjong_theory::{game, interaction};
 use rustyline::{self, DefaultEditor, error::ReadlineError};
 
 #[derive(Parser, Debug)]
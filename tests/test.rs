@@ -1,5 +1,12 @@
 extern crate japanese_mahjong_theory;
-use japanese_mahjong_theory::{Hai, Mentsu, PlayerNumber, Tehai};
+use japanese_mahjong_theory::{
+    defense,
+    defense::{ChanceLevel, Safety, SafetyReason},
+    paifu, score, BlockReport, DiscardProfile, FuritenKind, GameManager, Hai, Haiyama,
+    HaiyamaOperation, HandArchetype, ImprovementKind, Kan, Mentsu, Naku, Operation, OperationKind,
+    PlayerNumber, State, Suit, Table, Tehai, TehaiOperation, TieBreak, Toitsu, DEFAULT_WANPAI_SIZE,
+    JSON_VERSION, MAX_RINSHANHAI_NUMBER, TENPAI_PROBABILITY_MAX_DRAWS, UKEIRE_TREE_MAX_DEPTH,
+};
 
 #[test]
 fn test_hai() {
@@ -86,3 +93,2027 @@ fn test_chiitoutsu() {
     assert_eq!(machi[3].machihai.iter().fold(0, |x, (_, &y)| x + y), 17);
     assert_eq!(machi[4].machihai.iter().fold(0, |x, (_, &y)| x + y), 15);
 }
+
+#[test]
+fn test_tehai_is_closed() {
+    let mut tehai = Tehai::new("1111m234p567s78s11z", PlayerNumber::Four).unwrap();
+    assert!(tehai.is_closed());
+    tehai.kan(&Mentsu::Kantsu(Hai::Manzu(1)), &None).unwrap();
+    assert!(tehai.is_closed());
+    assert_eq!(tehai.fuuro, vec![Mentsu::Ankantsu(Hai::Manzu(1))]);
+
+    let mut tehai = Tehai::new("22m234p567s789s11z", PlayerNumber::Four).unwrap();
+    tehai.pon(&Mentsu::Koutsu(Hai::Manzu(2))).unwrap();
+    assert!(!tehai.is_closed());
+
+    let tehai = Tehai::new("234p567s789s11z[2222m]", PlayerNumber::Four).unwrap();
+    assert!(!tehai.is_closed());
+    assert_eq!(tehai.to_json()["fuuro"][0]["type"], "kantsu");
+
+    let tehai = Tehai::new("234p567s789s11z(2222m)", PlayerNumber::Four).unwrap();
+    assert!(tehai.is_closed());
+    assert_eq!(tehai.fuuro, vec![Mentsu::Ankantsu(Hai::Manzu(2))]);
+    assert_eq!(tehai.to_json()["fuuro"][0]["type"], "ankantsu");
+    assert_eq!(Mentsu::Ankantsu(Hai::Manzu(2)).to_string(), "(2m2m2m2m)");
+    assert!(Tehai::new("234p567s789s11z(222m)", PlayerNumber::Four).is_err());
+    assert!(Tehai::new("234p567s789s11z(2222m]", PlayerNumber::Four).is_err());
+}
+
+#[test]
+fn test_sutehai_order() {
+    let mut game_manager = GameManager::new(PlayerNumber::Four);
+    let tehai = Tehai::new("123m456p789s11122z", PlayerNumber::Four).unwrap();
+    game_manager
+        .operate(Operation::Tehai(TehaiOperation::Initialize(tehai)))
+        .unwrap();
+    game_manager
+        .operate(Operation::Tehai(TehaiOperation::Discard {
+            hai: Hai::Jihai(1),
+            tsumogiri: false,
+        }))
+        .unwrap();
+    game_manager
+        .operate(Operation::Tehai(TehaiOperation::Add {
+            hai: Hai::Jihai(3),
+            haiyama_sensitive: true,
+        }))
+        .unwrap();
+    game_manager
+        .operate(Operation::Tehai(TehaiOperation::Discard {
+            hai: Hai::Jihai(1),
+            tsumogiri: false,
+        }))
+        .unwrap();
+    assert_eq!(game_manager.sutehai(), &vec![Hai::Jihai(1), Hai::Jihai(1)]);
+    assert_eq!(game_manager.sutehai_type().len(), 1);
+
+    game_manager.back(true).unwrap();
+    assert_eq!(game_manager.sutehai(), &vec![Hai::Jihai(1)]);
+}
+
+#[test]
+fn test_is_last_tile() {
+    let mut game_manager = GameManager::new(PlayerNumber::Four);
+    game_manager.command("init 1112m456p789s111z").unwrap();
+    assert_eq!(game_manager.live_wall_remaining(), None);
+    game_manager.command("draw 3z").unwrap();
+    game_manager.command("discard 3z").unwrap();
+    assert!(!game_manager.is_last_tile());
+
+    game_manager.set_live_wall_remaining(Some(3));
+    game_manager.command("draw 1m").unwrap();
+    assert_eq!(game_manager.live_wall_remaining(), Some(2));
+    game_manager.command("ankan 1m 4z").unwrap();
+    assert_eq!(game_manager.live_wall_remaining(), Some(1));
+    game_manager.command("discard 4z").unwrap();
+    assert!(!game_manager.is_last_tile());
+
+    game_manager.command("draw 5z").unwrap();
+    assert!(game_manager.is_last_tile());
+    game_manager.command("discard 5z").unwrap();
+    assert!(game_manager.is_last_tile());
+
+    game_manager.back(true).unwrap();
+    game_manager.back(true).unwrap();
+    assert_eq!(game_manager.live_wall_remaining(), Some(1));
+    assert!(!game_manager.is_last_tile());
+}
+
+#[test]
+fn test_furiten_kind() {
+    let mut game_manager = GameManager::new(PlayerNumber::Four);
+    let tehai = Tehai::new("123m456p789s1122z", PlayerNumber::Four).unwrap();
+    game_manager
+        .operate(Operation::Tehai(TehaiOperation::Initialize(tehai)))
+        .unwrap();
+    assert_eq!(game_manager.furiten_kind(), FuritenKind::None);
+    game_manager
+        .operate(Operation::Haiyama {
+            kind: HaiyamaOperation::Discard(vec![Hai::Jihai(1)]),
+            haiyama_sensitive: true,
+        })
+        .unwrap();
+    assert_eq!(game_manager.furiten_kind(), FuritenKind::Temporary);
+    game_manager
+        .operate(Operation::Tehai(TehaiOperation::Add {
+            hai: Hai::Jihai(3),
+            haiyama_sensitive: true,
+        }))
+        .unwrap();
+    game_manager
+        .operate(Operation::Tehai(TehaiOperation::Discard {
+            hai: Hai::Jihai(3),
+            tsumogiri: false,
+        }))
+        .unwrap();
+    assert_eq!(game_manager.furiten_kind(), FuritenKind::None);
+
+    let mut game_manager = GameManager::new(PlayerNumber::Four);
+    let tehai = Tehai::new("123m456p789s11222z", PlayerNumber::Four).unwrap();
+    game_manager
+        .operate(Operation::Tehai(TehaiOperation::Initialize(tehai)))
+        .unwrap();
+    game_manager
+        .operate(Operation::Tehai(TehaiOperation::Discard {
+            hai: Hai::Jihai(2),
+            tsumogiri: false,
+        }))
+        .unwrap();
+    assert_eq!(game_manager.furiten_kind(), FuritenKind::Permanent);
+}
+
+#[test]
+fn test_tehai_counts() {
+    let tehai = Tehai::new("1239m1p55s1234567z", PlayerNumber::Four).unwrap();
+    let counts = tehai.to_counts();
+    assert_eq!(counts[0], 1);
+    assert_eq!(counts[8], 1);
+    assert_eq!(counts[9], 1);
+    assert_eq!(counts[22], 2);
+    assert_eq!(counts[33], 1);
+    assert_eq!(Tehai::from_counts(&counts).unwrap(), tehai);
+
+    let mut counts = [0; 34];
+    counts[0] = 3;
+    assert!(Tehai::from_counts(&counts).is_err());
+    counts[0] = 5;
+    assert!(Tehai::from_counts(&counts).is_err());
+}
+
+#[test]
+fn test_hai_is_yakuhai() {
+    // East round, south seat.
+    let (bakaze, jikaze) = (Hai::Jihai(1), Hai::Jihai(2));
+    assert!(Hai::Jihai(1).is_yakuhai(bakaze, jikaze));
+    assert!(Hai::Jihai(2).is_yakuhai(bakaze, jikaze));
+    assert!(!Hai::Jihai(3).is_yakuhai(bakaze, jikaze));
+    assert!(!Hai::Jihai(4).is_yakuhai(bakaze, jikaze));
+    for i in 5..=7 {
+        assert!(Hai::Jihai(i).is_yakuhai(bakaze, jikaze));
+    }
+    assert!(!Hai::Manzu(1).is_yakuhai(bakaze, jikaze));
+}
+
+#[test]
+fn test_analyze_many() {
+    let tehai_vec: Vec<Tehai> = [
+        "123599m22p45s1z555z",
+        "129m19p19s1234567z",
+        "112233m4478p3557s",
+        "11122233344455m",
+        "123m456p",
+    ]
+    .iter()
+    .map(|string| Tehai::new(string, PlayerNumber::Four).unwrap())
+    .collect();
+    let mut haiyama = Haiyama::new(PlayerNumber::Four);
+    haiyama
+        .discard_with_vec(&vec![Hai::Manzu(5), Hai::Pinzu(3), Hai::Jihai(1)], true)
+        .unwrap();
+
+    let parallel = Tehai::analyze_many(&tehai_vec, PlayerNumber::Four, &haiyama);
+    assert_eq!(parallel.len(), tehai_vec.len());
+    for (tehai, result) in tehai_vec.iter().zip(parallel) {
+        assert_eq!(
+            result,
+            tehai.analyze_with_haiyama(PlayerNumber::Four, &haiyama)
+        );
+    }
+}
+
+#[test]
+fn test_tsumogiri() {
+    let mut game_manager = GameManager::new(PlayerNumber::Four);
+    let tehai = Tehai::new("123m456p789s1122z", PlayerNumber::Four).unwrap();
+    game_manager
+        .operate(Operation::Tehai(TehaiOperation::Initialize(tehai)))
+        .unwrap();
+    for (add, discard, tsumogiri) in [(3, 3, true), (4, 1, false)] {
+        game_manager
+            .operate(Operation::Tehai(TehaiOperation::Add {
+                hai: Hai::Jihai(add),
+                haiyama_sensitive: true,
+            }))
+            .unwrap();
+        game_manager
+            .operate(Operation::Tehai(TehaiOperation::Discard {
+                hai: Hai::Jihai(discard),
+                tsumogiri: !tsumogiri,
+            }))
+            .unwrap();
+        match game_manager.history().last() {
+            Some((Operation::Tehai(TehaiOperation::Discard { tsumogiri: t, .. }), ..)) => {
+                assert_eq!(*t, tsumogiri)
+            }
+            _ => panic!("Last operation should be discard."),
+        }
+    }
+}
+
+#[test]
+fn test_tile_total() {
+    let mut game_manager = GameManager::new(PlayerNumber::Four);
+    let tehai = Tehai::new("23m55p777p456s111z", PlayerNumber::Four).unwrap();
+    game_manager
+        .operate(Operation::Tehai(TehaiOperation::Initialize(tehai)))
+        .unwrap();
+    let naku = |kind| {
+        Operation::Tehai(TehaiOperation::Naku {
+            kind,
+            haiyama_sensitive: true,
+        })
+    };
+    let add = |hai| {
+        Operation::Tehai(TehaiOperation::Add {
+            hai,
+            haiyama_sensitive: true,
+        })
+    };
+    let discard = |hai| {
+        Operation::Tehai(TehaiOperation::Discard {
+            hai,
+            tsumogiri: false,
+        })
+    };
+    let kan = |hai| {
+        naku(Naku::Kan(Kan::Unknown {
+            kantsu: Mentsu::Kantsu(hai),
+            rinshanhai: None,
+        }))
+    };
+    let operations = vec![
+        (
+            naku(Naku::Chii {
+                juntsu: Mentsu::Juntsu(Hai::Manzu(1), Hai::Manzu(2), Hai::Manzu(3)),
+                nakihai: Hai::Manzu(1),
+            }),
+            14,
+        ),
+        (discard(Hai::Jihai(1)), 13),
+        (naku(Naku::Pon(Mentsu::Koutsu(Hai::Pinzu(5)))), 14),
+        (discard(Hai::Jihai(1)), 13),
+        // Daiminkan
+        (kan(Hai::Pinzu(7)), 13),
+        (add(Hai::Pinzu(5)), 14),
+        // Kakan
+        (kan(Hai::Pinzu(5)), 13),
+        (add(Hai::Jihai(2)), 14),
+    ];
+    for (op, tile_total) in operations {
+        game_manager.operate(op).unwrap();
+        assert_eq!(game_manager.tehai().unwrap().tile_total(), tile_total);
+    }
+    assert_eq!(game_manager.tehai().unwrap().fuuro.len(), 3);
+    while game_manager.back(true).is_ok() {}
+    assert_eq!(game_manager.history().len(), 0);
+}
+
+#[test]
+fn test_jihai_round_trip() {
+    for i in 1..=7 {
+        let hai = Hai::Jihai(i);
+        assert_eq!(hai.to_string(), format!("{}z", i));
+        assert_eq!(
+            Hai::from_string_unordered(&hai.to_string(), PlayerNumber::Four).unwrap(),
+            vec![hai]
+        );
+    }
+    let tehai = Tehai::new("1234567z", PlayerNumber::Four).unwrap();
+    assert_eq!(tehai.to_string(), "1z2z3z4z5z6z7z");
+    assert_eq!(
+        Tehai::new(&tehai.to_string(), PlayerNumber::Four).unwrap(),
+        tehai
+    );
+}
+
+#[test]
+fn test_haiyama_from_counts() {
+    assert_eq!(Haiyama::new(PlayerNumber::Four).remaining(), 136);
+    assert_eq!(Haiyama::new(PlayerNumber::Three).remaining(), 108);
+
+    let mut counts = [4; 34];
+    counts[0] = 0;
+    counts[13] = 2;
+    let haiyama = Haiyama::from_counts(&counts, PlayerNumber::Four).unwrap();
+    assert_eq!(haiyama.remaining(), 130);
+    assert_eq!(haiyama[&Hai::Manzu(1)], 0);
+    assert_eq!(haiyama[&Hai::Pinzu(5)], 2);
+    assert!(Haiyama::from_counts(&counts, PlayerNumber::Three).is_err());
+
+    counts[13] = 5;
+    assert!(Haiyama::from_counts(&counts, PlayerNumber::Four).is_err());
+}
+
+#[test]
+fn test_operation_opcode() {
+    let kantsu = Mentsu::Kantsu(Hai::Souzu(7));
+    let naku = |kind| {
+        Operation::Tehai(TehaiOperation::Naku {
+            kind,
+            haiyama_sensitive: true,
+        })
+    };
+    let operations = vec![
+        Operation::Haiyama {
+            kind: HaiyamaOperation::Add(vec![Hai::Manzu(1)]),
+            haiyama_sensitive: true,
+        },
+        Operation::Haiyama {
+            kind: HaiyamaOperation::Discard(vec![Hai::Manzu(1)]),
+            haiyama_sensitive: false,
+        },
+        Operation::Tehai(TehaiOperation::Initialize(
+            Tehai::new("123m456p789s11122z", PlayerNumber::Four).unwrap(),
+        )),
+        Operation::Tehai(TehaiOperation::Add {
+            hai: Hai::Jihai(1),
+            haiyama_sensitive: true,
+        }),
+        Operation::Tehai(TehaiOperation::Discard {
+            hai: Hai::Jihai(1),
+            tsumogiri: true,
+        }),
+        naku(Naku::Chii {
+            juntsu: Mentsu::Juntsu(Hai::Manzu(1), Hai::Manzu(2), Hai::Manzu(3)),
+            nakihai: Hai::Manzu(1),
+        }),
+        naku(Naku::Pon(Mentsu::Koutsu(Hai::Jihai(5)))),
+        naku(Naku::Kan(Kan::Daiminkan {
+            kantsu,
+            rinshanhai: None,
+        })),
+        naku(Naku::Kan(Kan::Kakan {
+            kantsu,
+            rinshanhai: None,
+        })),
+        naku(Naku::Kan(Kan::Ankan {
+            kantsu,
+            rinshanhai: Some(Hai::Pinzu(1)),
+        })),
+        naku(Naku::Kan(Kan::Unknown {
+            kantsu,
+            rinshanhai: None,
+        })),
+        Operation::Tehai(TehaiOperation::Riichi {
+            hai: Hai::Jihai(1),
+            tsumogiri: false,
+        }),
+    ];
+    for (index, op) in operations.iter().enumerate() {
+        assert_eq!(op.opcode(), index as u8);
+        assert_eq!(OperationKind::from_opcode(op.opcode()), Some(op.kind()));
+    }
+    assert_eq!(OperationKind::from_opcode(operations.len() as u8), None);
+}
+
+#[test]
+fn test_winning_group() {
+    let tehai = Tehai::new("123m456p789s11122z", PlayerNumber::Four).unwrap();
+    assert_eq!(
+        tehai.winning_group(&Hai::Pinzu(5), PlayerNumber::Four),
+        Some(Mentsu::Juntsu(Hai::Pinzu(4), Hai::Pinzu(5), Hai::Pinzu(6)))
+    );
+    assert_eq!(
+        tehai.winning_group(&Hai::Souzu(7), PlayerNumber::Four),
+        Some(Mentsu::Juntsu(Hai::Souzu(7), Hai::Souzu(8), Hai::Souzu(9)))
+    );
+    assert_eq!(
+        tehai.winning_group(&Hai::Jihai(1), PlayerNumber::Four),
+        Some(Mentsu::Koutsu(Hai::Jihai(1)))
+    );
+    assert_eq!(
+        tehai.winning_group(&Hai::Jihai(2), PlayerNumber::Four),
+        None
+    );
+    assert_eq!(
+        tehai.winning_group(&Hai::Jihai(3), PlayerNumber::Four),
+        None
+    );
+
+    let mut not_hourakei = tehai.clone();
+    not_hourakei.juntehai[0] = Hai::Manzu(9);
+    assert_eq!(
+        not_hourakei.winning_group(&Hai::Pinzu(5), PlayerNumber::Four),
+        None
+    );
+}
+
+#[test]
+fn test_command() {
+    let mut game_manager = GameManager::new(PlayerNumber::Four);
+    game_manager.command("init 11134m456p789s11z").unwrap();
+    game_manager.command("draw 1m").unwrap();
+    assert!(game_manager.command("kakan 1m").is_err());
+    assert_eq!(game_manager.history().len(), 2);
+    game_manager.command("ankan 1m 7z").unwrap();
+    game_manager.command("discard 7z").unwrap();
+    game_manager.command("seen 57z").unwrap();
+    game_manager.command("pon 1z").unwrap();
+    game_manager.command("discard 4p").unwrap();
+    game_manager.command("chii 234m 2m").unwrap();
+    game_manager.command("discard 5p").unwrap();
+    assert_eq!(game_manager.history().len(), 9);
+
+    assert!(game_manager.command("").is_err());
+    assert!(game_manager.command("riichi 3m").is_err());
+    assert!(game_manager.command("draw").is_err());
+    assert!(game_manager.command("draw 1m2m").is_err());
+    assert!(game_manager.command("chii 244m 2m").is_err());
+    assert_eq!(game_manager.history().len(), 9);
+}
+
+#[test]
+fn test_machi_condition_loss() {
+    let tehai = Tehai::new("12358m456p78s1123z", PlayerNumber::Four).unwrap();
+    let (_, conditions) = tehai.analyze(PlayerNumber::Four, None).unwrap();
+    assert_eq!(conditions[0].loss, 0);
+    assert!(conditions.iter().any(|condition| condition.loss > 0));
+    let best = conditions[0].nokori();
+    for condition in &conditions {
+        assert_eq!(condition.loss, best - condition.nokori());
+    }
+}
+
+#[test]
+fn test_hai_suit_and_number() {
+    assert_eq!(Hai::Pinzu(5).suit(), Suit::Pinzu);
+    assert_eq!(Hai::Pinzu(5).number(), Some(5));
+    assert_eq!(Hai::Jihai(5).suit(), Suit::Jihai);
+    assert_eq!(Hai::Jihai(5).number(), None);
+}
+
+#[test]
+fn test_hai_next_and_previous() {
+    let four = PlayerNumber::Four;
+    assert_eq!(Hai::Manzu(8).next(four, false), Some(Hai::Manzu(9)));
+    assert_eq!(Hai::Manzu(9).next(four, false), None);
+    assert_eq!(Hai::Manzu(9).next(four, true), Some(Hai::Manzu(1)));
+    assert_eq!(Hai::Pinzu(1).previous(four, false), None);
+    assert_eq!(Hai::Jihai(4).next(four, true), Some(Hai::Jihai(1)));
+    assert_eq!(Hai::Jihai(7).next(four, true), Some(Hai::Jihai(5)));
+    assert_eq!(Hai::Jihai(1).previous(four, true), Some(Hai::Jihai(4)));
+    assert_eq!(Hai::Jihai(7).next(four, false), None);
+    assert_eq!(
+        Hai::Manzu(1).next(PlayerNumber::Three, true),
+        Some(Hai::Manzu(9))
+    );
+}
+
+#[test]
+fn test_chii_invalid_juntsu() {
+    let mut tehai = Tehai::new("1245m456p789s1122z", PlayerNumber::Four).unwrap();
+    let backup = tehai.clone();
+    for (juntsu, nakihai) in [
+        (
+            Mentsu::Juntsu(Hai::Manzu(1), Hai::Manzu(2), Hai::Manzu(4)),
+            Hai::Manzu(4),
+        ),
+        (
+            Mentsu::Juntsu(Hai::Manzu(1), Hai::Manzu(2), Hai::Pinzu(3)),
+            Hai::Pinzu(3),
+        ),
+        (
+            Mentsu::Juntsu(Hai::Jihai(1), Hai::Jihai(2), Hai::Jihai(3)),
+            Hai::Jihai(3),
+        ),
+        (
+            Mentsu::Juntsu(Hai::Manzu(1), Hai::Manzu(2), Hai::Manzu(3)),
+            Hai::Manzu(5),
+        ),
+    ] {
+        assert!(tehai.chii(&juntsu, &nakihai).is_err());
+        assert_eq!(tehai, backup);
+    }
+    tehai
+        .chii(
+            &Mentsu::Juntsu(Hai::Manzu(1), Hai::Manzu(2), Hai::Manzu(3)),
+            &Hai::Manzu(3),
+        )
+        .unwrap();
+}
+
+#[test]
+fn test_analysis_json() {
+    let tehai = Tehai::new("12358m456p78s1123z", PlayerNumber::Four).unwrap();
+    let haiyama = Haiyama::new(PlayerNumber::Four);
+    let json = tehai.analysis_json(PlayerNumber::Four, &haiyama).unwrap();
+    assert!(json["tehai"]["juntehai"].is_array());
+    assert_eq!(json["shanten_number"], 2);
+    let condition = &json["conditions"][0];
+    for key in ["sutehai", "furiten", "machihai_number", "loss", "machihai"] {
+        assert!(condition.get(key).is_some(), "missing key {}", key);
+    }
+    assert!(condition["machihai"][0].get("tile").is_some());
+    assert!(condition["machihai"][0].get("number").is_some());
+}
+
+#[test]
+fn test_riichi() {
+    let mut game_manager = GameManager::new(PlayerNumber::Four);
+    game_manager.command("init 123m456p789s11123z").unwrap();
+    assert!(game_manager.command("riichi 1m").is_err());
+    game_manager.command("riichi 3z").unwrap();
+    assert_eq!(game_manager.riichi(), Some(0));
+    assert_eq!(
+        game_manager.history().last().unwrap().0.kind(),
+        OperationKind::Riichi
+    );
+    game_manager.back(true).unwrap();
+    assert_eq!(game_manager.riichi(), None);
+
+    game_manager.command("discard 3z").unwrap();
+    game_manager.command("pon 1z").unwrap();
+    assert!(game_manager.command("riichi 1z").is_err());
+    assert!(game_manager.riichi().is_none());
+}
+
+#[test]
+fn test_riichi_lock() {
+    let mut game_manager = GameManager::new(PlayerNumber::Four);
+    game_manager.command("init 1112m456p789s1113z").unwrap();
+    game_manager.command("riichi 3z").unwrap();
+    game_manager.command("draw 4z").unwrap();
+    assert!(game_manager.command("discard 2m").is_err());
+    game_manager.command("discard 4z").unwrap();
+    assert!(game_manager.command("pon 1z").is_err());
+    assert!(game_manager.command("chii 123m 3m").is_err());
+    assert!(game_manager.command("kan 1z").is_err());
+
+    game_manager.command("draw 1z").unwrap();
+    game_manager.command("ankan 1z 5z").unwrap();
+    game_manager.command("discard 5z").unwrap();
+    game_manager.command("draw 1m").unwrap();
+    assert!(game_manager.command("ankan 1m").is_err());
+    game_manager.command("discard 1m").unwrap();
+}
+
+#[test]
+fn test_haiyama_remaining_by_suit() {
+    let mut haiyama = Haiyama::new(PlayerNumber::Four);
+    assert_eq!(haiyama.remaining_by_suit(), [36, 36, 36, 28]);
+    for hai in Hai::from_string_unordered("1125p", PlayerNumber::Four).unwrap() {
+        haiyama.discard(&hai).unwrap();
+    }
+    assert_eq!(haiyama.remaining_by_suit(), [36, 32, 36, 28]);
+    assert_eq!(
+        Haiyama::new(PlayerNumber::Three).remaining_by_suit(),
+        [8, 36, 36, 28]
+    );
+}
+
+#[test]
+fn test_table_deal() {
+    let mut wall = vec![];
+    for _ in 0..4 {
+        wall.extend(Hai::all_type(PlayerNumber::Four));
+    }
+    let mut table = Table::deal(&wall, PlayerNumber::Four).unwrap();
+    for seat in 0..4 {
+        assert_eq!(table.tehai(seat).unwrap().juntehai.len(), 13);
+    }
+    assert!(table.tehai(4).is_none());
+    assert_eq!(table.wall_remaining(), 136 - 52);
+    assert_eq!(table.haiyama().remaining(), 136 - 52);
+    for seat in 0..4 {
+        let juntehai = &table.tehai(seat).unwrap().juntehai;
+        assert!(juntehai.contains(&wall[seat * 4]));
+        assert!(juntehai.contains(&wall[48 + seat]));
+    }
+
+    let hai = table.draw(0).unwrap().unwrap();
+    assert_eq!(table.tehai(0).unwrap().juntehai.len(), 14);
+    assert_eq!(table.haiyama().remaining(), 136 - 53);
+    table.discard(0, &hai).unwrap();
+    assert_eq!(table.sutehai(0).unwrap(), &vec![hai]);
+    assert!(table.discard(1, &Hai::Jihai(7)).is_err());
+
+    assert!(Table::deal(&wall[..50], PlayerNumber::Four).is_err());
+    wall.push(Hai::Manzu(1));
+    assert!(Table::deal(&wall, PlayerNumber::Four).is_err());
+
+    let mut wall = vec![];
+    for _ in 0..4 {
+        wall.extend(Hai::all_type(PlayerNumber::Three));
+    }
+    assert!(Table::deal(&wall, PlayerNumber::Three).is_ok());
+    wall[0] = Hai::Manzu(2);
+    assert_eq!(
+        Table::deal(&wall, PlayerNumber::Three).unwrap_err(),
+        "'2m' is invalid hai."
+    );
+}
+
+#[test]
+fn test_kan_changes_wait() {
+    let tehai = Tehai::new("11112m456p789s111z", PlayerNumber::Four).unwrap();
+    assert!(tehai
+        .kan_changes_wait(&Hai::Manzu(1), PlayerNumber::Four)
+        .unwrap());
+    let tehai = Tehai::new("1112m456p789s1111z", PlayerNumber::Four).unwrap();
+    assert!(!tehai
+        .kan_changes_wait(&Hai::Jihai(1), PlayerNumber::Four)
+        .unwrap());
+    assert!(tehai
+        .kan_changes_wait(&Hai::Manzu(1), PlayerNumber::Four)
+        .is_err());
+}
+
+#[test]
+fn test_ukeire_tree() {
+    let tehai = Tehai::new("123m456p789s12345z", PlayerNumber::Four).unwrap();
+    let mut haiyama = Haiyama::new(PlayerNumber::Four);
+    haiyama.discard_with_vec(&tehai.juntehai, true).unwrap();
+    let tree = tehai.ukeire_tree(PlayerNumber::Four, &haiyama, 2).unwrap();
+    assert_eq!(tree.shanten, 2);
+    assert!(!tree.children.is_empty());
+    for (number, child) in tree.children.values() {
+        assert_eq!(*number, 3);
+        assert_eq!(child.shanten, 1);
+        assert!(!child.children.is_empty());
+        for (_, grandchild) in child.children.values() {
+            assert_eq!(grandchild.shanten, 0);
+            assert!(grandchild.children.is_empty());
+        }
+    }
+    assert!(tehai
+        .ukeire_tree(PlayerNumber::Four, &haiyama, UKEIRE_TREE_MAX_DEPTH + 1)
+        .is_err());
+}
+
+#[test]
+fn test_ukeire_tree_high_shanten() {
+    let tehai = Tehai::new("13579m2468p13579s", PlayerNumber::Four).unwrap();
+    let mut haiyama = Haiyama::new(PlayerNumber::Four);
+    haiyama.discard_with_vec(&tehai.juntehai, true).unwrap();
+    let start = std::time::Instant::now();
+    let tree = tehai
+        .ukeire_tree(PlayerNumber::Four, &haiyama, UKEIRE_TREE_MAX_DEPTH)
+        .unwrap();
+    assert!(start.elapsed() < std::time::Duration::from_secs(10));
+    assert!(!tree.children.is_empty());
+    for (_, child) in tree.children.values() {
+        assert_eq!(child.shanten, tree.shanten - 1);
+    }
+}
+
+#[test]
+fn test_compact_history() {
+    let mut game_manager = GameManager::new(PlayerNumber::Four);
+    let haiyama_operation = |kind| Operation::Haiyama {
+        kind,
+        haiyama_sensitive: true,
+    };
+    game_manager
+        .operate(haiyama_operation(HaiyamaOperation::Discard(vec![
+            Hai::Manzu(
+                1
+            );
+            4
+        ])))
+        .unwrap();
+    for _ in 0..3 {
+        game_manager
+            .operate(haiyama_operation(HaiyamaOperation::Add(vec![Hai::Manzu(
+                1,
+            )])))
+            .unwrap();
+    }
+    game_manager
+        .operate(Operation::Haiyama {
+            kind: HaiyamaOperation::Add(vec![Hai::Manzu(1)]),
+            haiyama_sensitive: false,
+        })
+        .unwrap();
+    let haiyama = game_manager.haiyama().to_string();
+
+    game_manager.compact_history();
+    assert_eq!(game_manager.history().len(), 3);
+    match &game_manager.history()[1].0 {
+        Operation::Haiyama {
+            kind: HaiyamaOperation::Add(hai_vec),
+            ..
+        } => assert_eq!(hai_vec, &vec![Hai::Manzu(1); 3]),
+        _ => panic!("Second operation should be merged add."),
+    }
+    assert_eq!(game_manager.haiyama().to_string(), haiyama);
+
+    game_manager.back(true).unwrap();
+    assert_eq!(game_manager.haiyama()[&Hai::Manzu(1)], 3);
+    game_manager.back(true).unwrap();
+    assert_eq!(game_manager.haiyama()[&Hai::Manzu(1)], 0);
+}
+
+#[test]
+fn test_tie_break() {
+    let tehai = Tehai::new("115599m115599p5s7z", PlayerNumber::Four).unwrap();
+    let sutehai_order = |tie_break| {
+        let (_, conditions) = tehai
+            .analyze_with_tie_break(PlayerNumber::Four, None, tie_break)
+            .unwrap();
+        conditions
+            .iter()
+            .map(|condition| condition.sutehai)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        sutehai_order(TieBreak::HaiOrder),
+        vec![Hai::Souzu(5), Hai::Jihai(7)]
+    );
+    assert_eq!(
+        sutehai_order(TieBreak::YaochuupaiFirst),
+        vec![Hai::Jihai(7), Hai::Souzu(5)]
+    );
+}
+
+#[test]
+fn test_block_analysis() {
+    let tehai = Tehai::new("12m46m456p789s1122z", PlayerNumber::Four).unwrap();
+    let report = tehai.block_analysis(PlayerNumber::Four).unwrap();
+    assert_eq!(
+        report,
+        BlockReport {
+            mentsu: 2,
+            toitsu: 2,
+            taatsu: 2,
+            ukihai: 0,
+            target: 5,
+        }
+    );
+    assert_eq!(report.overflow(), 1);
+
+    let tehai = Tehai::new("123m456p13789s11z5z", PlayerNumber::Four).unwrap();
+    let report = tehai.block_analysis(PlayerNumber::Four).unwrap();
+    assert_eq!((report.mentsu, report.toitsu, report.taatsu), (3, 1, 1));
+    assert_eq!(report.ukihai, 1);
+    assert_eq!(report.overflow(), 0);
+}
+
+#[test]
+fn test_analyze_excluding() {
+    let tehai = Tehai::new("12358m456p78s1123z", PlayerNumber::Four).unwrap();
+    let (shanten, conditions) = tehai.analyze(PlayerNumber::Four, None).unwrap();
+    let excluded = [Hai::Manzu(8), Hai::Jihai(2)].iter().copied().collect();
+    let (excluded_shanten, excluded_conditions) = tehai
+        .analyze_excluding(PlayerNumber::Four, None, &excluded)
+        .unwrap();
+    assert_eq!(excluded_shanten, shanten);
+    assert_eq!(excluded_conditions.len() + excluded.len(), conditions.len());
+    assert!(excluded_conditions
+        .iter()
+        .all(|condition| !excluded.contains(&condition.sutehai)));
+    assert_eq!(excluded_conditions[0].loss, 0);
+
+    let pinzu = Hai::all_type(PlayerNumber::Four)
+        .into_iter()
+        .filter(|hai| matches!(hai, Hai::Pinzu(_)))
+        .collect();
+    let tehai = Tehai::new("123m9p456p789s11z57z", PlayerNumber::Four).unwrap();
+    let (_, conditions) = tehai
+        .analyze_excluding(PlayerNumber::Four, None, &pinzu)
+        .unwrap();
+    assert_eq!(conditions.len(), 2);
+    assert!(conditions
+        .iter()
+        .all(|condition| !matches!(condition.sutehai, Hai::Pinzu(_))));
+
+    let tehai = Tehai::new("12358m456p78s1123z", PlayerNumber::Four).unwrap();
+    let (shanten, conditions) = tehai.analyze(PlayerNumber::Four, None).unwrap();
+    let excluded = conditions
+        .iter()
+        .map(|condition| condition.sutehai)
+        .collect();
+    let (excluded_shanten, excluded_conditions) = tehai
+        .analyze_excluding(PlayerNumber::Four, None, &excluded)
+        .unwrap();
+    assert_eq!(excluded_shanten, shanten + 1);
+    assert!(!excluded_conditions.is_empty());
+    assert!(excluded_conditions
+        .iter()
+        .all(|condition| !excluded.contains(&condition.sutehai) && condition.nokori() > 0));
+    assert_eq!(excluded_conditions[0].loss, 0);
+}
+
+#[test]
+fn test_verify_replay() {
+    let mut expected = GameManager::new(PlayerNumber::Four);
+    for line in [
+        "init 123m456p789s11223z",
+        "discard 3z",
+        "draw 4z",
+        "discard 4z",
+    ] {
+        expected.command(line).unwrap();
+    }
+    let mut ops: Vec<Operation> = expected
+        .history()
+        .iter()
+        .map(|(op, ..)| op.clone())
+        .collect();
+    assert!(GameManager::verify_replay(&expected, &ops).is_ok());
+
+    ops[2] = Operation::Tehai(TehaiOperation::Add {
+        hai: Hai::Jihai(5),
+        haiyama_sensitive: true,
+    });
+    assert_eq!(
+        GameManager::verify_replay(&expected, &ops).unwrap_err().0,
+        2
+    );
+    ops[2] = Operation::Tehai(TehaiOperation::Add {
+        hai: Hai::Jihai(4),
+        haiyama_sensitive: true,
+    });
+    ops[3] = Operation::Tehai(TehaiOperation::Discard {
+        hai: Hai::Jihai(1),
+        tsumogiri: false,
+    });
+    assert_eq!(
+        GameManager::verify_replay(&expected, &ops).unwrap_err().0,
+        3
+    );
+    assert_eq!(
+        GameManager::verify_replay(&expected, &ops[..3])
+            .unwrap_err()
+            .0,
+        3
+    );
+}
+
+#[test]
+fn test_archetype() {
+    let archetype = |string| {
+        Tehai::new(string, PlayerNumber::Four)
+            .unwrap()
+            .archetype(PlayerNumber::Four)
+            .unwrap()
+    };
+    assert_eq!(archetype("1199m1199p1122z57z"), HandArchetype::Chiitoitsu);
+    assert_eq!(archetype("119m19p19s1234567z"), HandArchetype::Kokushimusou);
+    assert_eq!(archetype("1235678999m1p122z"), HandArchetype::Honitsu);
+    assert_eq!(archetype("123m456p789s11223z"), HandArchetype::Standard);
+}
+
+#[test]
+fn test_efficiency_vs() {
+    let haiyama = Haiyama::new(PlayerNumber::Four);
+    let ryanmen = Tehai::new("123m456p789s11z34s", PlayerNumber::Four).unwrap();
+    let kanchan = Tehai::new("123m456p789s11z35s", PlayerNumber::Four).unwrap();
+    let iishanten = Tehai::new("123m456p789s1z34s5z", PlayerNumber::Four).unwrap();
+    assert_eq!(
+        ryanmen
+            .efficiency_vs(&kanchan, PlayerNumber::Four, &haiyama)
+            .unwrap(),
+        std::cmp::Ordering::Greater
+    );
+    assert_eq!(
+        kanchan
+            .efficiency_vs(&ryanmen, PlayerNumber::Four, &haiyama)
+            .unwrap(),
+        std::cmp::Ordering::Less
+    );
+    assert_eq!(
+        kanchan
+            .efficiency_vs(&iishanten, PlayerNumber::Four, &haiyama)
+            .unwrap(),
+        std::cmp::Ordering::Greater
+    );
+    assert_eq!(
+        kanchan
+            .efficiency_vs(&kanchan, PlayerNumber::Four, &haiyama)
+            .unwrap(),
+        std::cmp::Ordering::Equal
+    );
+}
+
+#[test]
+fn test_table_wanpai() {
+    let mut wall = vec![];
+    for _ in 0..4 {
+        wall.extend(Hai::all_type(PlayerNumber::Four));
+    }
+    let table = Table::deal(&wall, PlayerNumber::Four).unwrap();
+    assert_eq!(table.live_wall_remaining(), 136 - 52 - DEFAULT_WANPAI_SIZE);
+    assert_eq!(table.turns_remaining(), 17);
+
+    let mut table = Table::deal_with_wanpai(&wall, PlayerNumber::Four, 18).unwrap();
+    assert_eq!(table.live_wall_remaining(), 66);
+    assert_eq!(table.turns_remaining(), 16);
+    for turn in 0..66 {
+        assert!(!table.is_last_tile());
+        let hai = table.draw(turn % 4).unwrap().unwrap();
+        if turn == 65 {
+            // Haitei, with 18 hai of wanpai left.
+            assert!(table.is_last_tile());
+            assert_eq!(table.wall_remaining(), 18);
+        }
+        table.discard(turn % 4, &hai).unwrap();
+    }
+    // Houtei.
+    assert!(table.is_last_tile());
+    assert!(!table.is_exhausted());
+    assert_eq!(table.draw(2), Ok(None));
+    assert!(table.is_exhausted());
+    assert_eq!(table.tehai(2).unwrap().juntehai.len(), 13);
+    assert_eq!(table.wall_remaining(), 18);
+    assert!(table.draw(4).is_err());
+}
+
+#[test]
+fn test_haiyama_dead_tiles() {
+    let mut haiyama = Haiyama::new(PlayerNumber::Three);
+    assert!(haiyama.dead_tiles().is_empty());
+    haiyama
+        .discard_with_vec(&vec![Hai::Souzu(5); 4], true)
+        .unwrap();
+    haiyama
+        .discard_with_vec(&vec![Hai::Jihai(1); 3], true)
+        .unwrap();
+    assert_eq!(haiyama.dead_tiles(), vec![Hai::Souzu(5)]);
+}
+
+#[test]
+fn test_tenpai_probability() {
+    let tehai = Tehai::new("123m456p789s1z34s5z", PlayerNumber::Four).unwrap();
+    let mut haiyama = Haiyama::new(PlayerNumber::Four);
+    haiyama.discard_with_vec(&tehai.juntehai, true).unwrap();
+    let mut last = 0.0;
+    for draws in 0..=3 {
+        let probability = tehai
+            .tenpai_probability(PlayerNumber::Four, &haiyama, draws)
+            .unwrap();
+        assert!(probability >= last);
+        assert!(probability <= 1.0 + 1e-9);
+        last = probability;
+    }
+    assert!(last > 0.0);
+    assert_eq!(
+        tehai
+            .tenpai_probability(PlayerNumber::Four, &haiyama, 0)
+            .unwrap(),
+        0.0
+    );
+    let tenpai = Tehai::new("123m456p789s1z345s", PlayerNumber::Four).unwrap();
+    assert_eq!(
+        tenpai
+            .tenpai_probability(PlayerNumber::Four, &haiyama, 0)
+            .unwrap(),
+        1.0
+    );
+    assert!(tehai
+        .tenpai_probability(
+            PlayerNumber::Four,
+            &haiyama,
+            TENPAI_PROBABILITY_MAX_DRAWS + 1
+        )
+        .is_err());
+
+    let yonshanten = Tehai::new("13579m2468p1357s", PlayerNumber::Four).unwrap();
+    let mut haiyama = Haiyama::new(PlayerNumber::Four);
+    haiyama
+        .discard_with_vec(&yonshanten.juntehai, true)
+        .unwrap();
+    let start = std::time::Instant::now();
+    assert_eq!(
+        yonshanten
+            .tenpai_probability(PlayerNumber::Four, &haiyama, 3)
+            .unwrap(),
+        0.0
+    );
+    assert!(start.elapsed() < std::time::Duration::from_secs(1));
+}
+
+#[test]
+fn test_sanma_hai_type() {
+    let all_type = Hai::all_type(PlayerNumber::Three);
+    assert_eq!(all_type.len(), 27);
+    for index in 1..=9 {
+        assert_eq!(
+            all_type.contains(&Hai::Manzu(index)),
+            index == 1 || index == 9
+        );
+        assert!(all_type.contains(&Hai::Pinzu(index)));
+        assert!(all_type.contains(&Hai::Souzu(index)));
+    }
+    for index in 1..=7 {
+        assert!(all_type.contains(&Hai::Jihai(index)));
+    }
+
+    let haiyama = Haiyama::new(PlayerNumber::Three);
+    let four_haiyama = Haiyama::new(PlayerNumber::Four);
+    for index in 1..=7 {
+        let hai = Hai::Jihai(index);
+        assert_eq!(haiyama[&hai], four_haiyama[&hai]);
+    }
+    assert_eq!(haiyama.remaining_by_suit(), [8, 36, 36, 28]);
+    assert!(Tehai::new("2m", PlayerNumber::Three).is_err());
+    assert!(Tehai::new("1m", PlayerNumber::Three).is_ok());
+}
+
+#[test]
+fn test_suggest_blocks() {
+    let tehai = Tehai::new("123m456p789s34s11z7z", PlayerNumber::Four).unwrap();
+    let (mentsu_vec, jantou) = tehai.suggest_blocks(PlayerNumber::Four).unwrap();
+    assert_eq!(mentsu_vec.len(), 4);
+    assert!(mentsu_vec.contains(&Mentsu::Juntsu(Hai::Manzu(1), Hai::Manzu(2), Hai::Manzu(3))));
+    assert!(mentsu_vec.contains(&Mentsu::Juntsu(Hai::Souzu(3), Hai::Souzu(4), Hai::Souzu(5))));
+    assert_eq!(jantou, Some(Toitsu(Hai::Jihai(1))));
+
+    let tehai = Tehai::new("123m456p79s89s1155z", PlayerNumber::Four).unwrap();
+    let (mentsu_vec, jantou) = tehai.suggest_blocks(PlayerNumber::Four).unwrap();
+    assert_eq!(mentsu_vec.len(), 4);
+    assert!(mentsu_vec.contains(&Mentsu::Juntsu(Hai::Souzu(7), Hai::Souzu(8), Hai::Souzu(9))));
+    assert!(mentsu_vec.contains(&Mentsu::Koutsu(Hai::Jihai(5))));
+    assert_eq!(jantou, Some(Toitsu(Hai::Jihai(1))));
+}
+
+#[test]
+fn test_hai_index() {
+    for index in 0..34 {
+        let hai = Hai::from_index(index).unwrap();
+        assert_eq!(hai.to_index(), index);
+    }
+    assert_eq!(Hai::from_index(0), Some(Hai::Manzu(1)));
+    assert_eq!(Hai::from_index(9), Some(Hai::Pinzu(1)));
+    assert_eq!(Hai::from_index(18), Some(Hai::Souzu(1)));
+    assert_eq!(Hai::from_index(33), Some(Hai::Jihai(7)));
+    assert_eq!(Hai::from_index(34), None);
+}
+
+#[test]
+fn test_completions() {
+    let tehai = Tehai::new("1112345678999m", PlayerNumber::Four).unwrap();
+    let completions = tehai.completions(PlayerNumber::Four).unwrap();
+    assert_eq!(
+        completions.len(),
+        tehai.machi(PlayerNumber::Four).unwrap().len()
+    );
+    assert_eq!(completions.len(), 9);
+    for (hai, completion) in &completions {
+        assert_eq!(completion.juntehai.len(), 14);
+        assert_eq!(completion.analyze(PlayerNumber::Four, None).unwrap().0, -1);
+        assert!(completion.juntehai.contains(hai));
+    }
+
+    let tehai = Tehai::new("123m456p789s1z345z", PlayerNumber::Four).unwrap();
+    assert!(tehai.completions(PlayerNumber::Four).unwrap().is_empty());
+}
+
+#[test]
+fn test_partial_shanten_and_ukeire() {
+    let haiyama = Haiyama::new(PlayerNumber::Four);
+    let tehai = Tehai::new("123m456p78s11z[222z]", PlayerNumber::Four).unwrap();
+    assert_eq!(tehai.juntehai.len(), 10);
+    let (shanten, ukeire) = tehai
+        .shanten_and_ukeire(PlayerNumber::Four, &haiyama)
+        .unwrap();
+    assert_eq!(shanten, 0);
+    assert_eq!(
+        ukeire.keys().copied().collect::<Vec<_>>(),
+        vec![Hai::Souzu(6), Hai::Souzu(9)]
+    );
+
+    let tehai = Tehai::new("13m57p1z23z", PlayerNumber::Four).unwrap();
+    let (shanten, ukeire) = tehai
+        .shanten_and_ukeire(PlayerNumber::Four, &haiyama)
+        .unwrap();
+    assert_eq!(shanten, 2);
+    assert!(ukeire.contains_key(&Hai::Manzu(2)));
+    assert!(ukeire.contains_key(&Hai::Jihai(1)));
+    assert!(!ukeire.contains_key(&Hai::Souzu(5)));
+}
+
+#[test]
+fn test_winning_tile_live_count() {
+    let tehai = Tehai::new("123m456p789s23s11z", PlayerNumber::Four).unwrap();
+    let mut haiyama = Haiyama::new(PlayerNumber::Four);
+    haiyama.discard_with_vec(&tehai.juntehai, true).unwrap();
+    assert_eq!(
+        tehai
+            .winning_tile_live_count(PlayerNumber::Four, &haiyama)
+            .unwrap(),
+        vec![(Hai::Souzu(1), 4), (Hai::Souzu(4), 4)]
+    );
+
+    haiyama
+        .discard_with_vec(&vec![Hai::Souzu(1); 3], true)
+        .unwrap();
+    assert_eq!(
+        tehai
+            .winning_tile_live_count(PlayerNumber::Four, &haiyama)
+            .unwrap(),
+        vec![(Hai::Souzu(1), 1), (Hai::Souzu(4), 4)]
+    );
+}
+
+#[test]
+fn test_hai_serde() {
+    let hai = Hai::Jihai(5);
+    let json = serde_json::to_string(&hai).unwrap();
+    assert_eq!(json, "\"5z\"");
+    assert_eq!(serde_json::from_str::<Hai>(&json).unwrap(), hai);
+    assert_eq!(
+        serde_json::from_str::<Vec<Hai>>("[\"1m\",\"9s\"]").unwrap(),
+        vec![Hai::Manzu(1), Hai::Souzu(9)]
+    );
+    assert!(serde_json::from_str::<Hai>("\"12m\"").is_err());
+    assert!(serde_json::from_str::<Hai>("\"8z\"").is_err());
+}
+
+#[test]
+fn test_chance_level() {
+    let mut visible = [0; 34];
+    let pinzu = |number| Hai::Pinzu(number);
+    assert_eq!(
+        defense::chance_level(&pinzu(2), &visible),
+        Some(ChanceLevel::Normal)
+    );
+
+    visible[pinzu(3).to_index() as usize] = 4;
+    assert_eq!(
+        defense::chance_level(&pinzu(1), &visible),
+        Some(ChanceLevel::NoChance)
+    );
+    assert_eq!(
+        defense::chance_level(&pinzu(2), &visible),
+        Some(ChanceLevel::NoChance)
+    );
+    assert_eq!(
+        defense::chance_level(&pinzu(4), &visible),
+        Some(ChanceLevel::Normal)
+    );
+
+    visible[pinzu(6).to_index() as usize] = 3;
+    assert_eq!(
+        defense::chance_level(&pinzu(4), &visible),
+        Some(ChanceLevel::OneChance)
+    );
+    assert_eq!(defense::chance_level(&Hai::Jihai(1), &visible), None);
+}
+
+#[test]
+fn test_new_round() {
+    let mut game_manager = GameManager::new(PlayerNumber::Three);
+    game_manager.command("init 123p456s789s19m11z").unwrap();
+    game_manager.command("draw 1p").unwrap();
+    game_manager.command("discard 1z").unwrap();
+    assert!(!game_manager.history().is_empty());
+
+    game_manager.new_round();
+    assert_eq!(game_manager.state, State::WaitToInit);
+    assert_eq!(game_manager.haiyama().remaining(), 108);
+    assert!(game_manager.tehai().is_none());
+    assert!(game_manager.sutehai().is_empty());
+    assert!(game_manager.history().is_empty());
+    assert_eq!(game_manager.player_number(), PlayerNumber::Three);
+}
+
+#[test]
+fn test_tiles_that_reduce_tenpai() {
+    let tehai = Tehai::new("123m456p789s23s11z", PlayerNumber::Four).unwrap();
+    let hai_vec = tehai.tiles_that_reduce_tenpai(PlayerNumber::Four).unwrap();
+    assert!(hai_vec.contains(&Hai::Manzu(9)));
+    assert!(hai_vec.contains(&Hai::Jihai(7)));
+    // Kept by discarding 2s or 3s.
+    assert!(!hai_vec.contains(&Hai::Souzu(5)));
+    assert!(!hai_vec.contains(&Hai::Jihai(1)));
+    assert!(!hai_vec.contains(&Hai::Souzu(1)));
+    assert!(!hai_vec.contains(&Hai::Souzu(4)));
+
+    let tehai = Tehai::new("123m456p789s1z345z", PlayerNumber::Four).unwrap();
+    assert!(tehai.tiles_that_reduce_tenpai(PlayerNumber::Four).is_err());
+}
+
+#[test]
+fn test_table_draw_rinshan() {
+    let mut wall = vec![];
+    for _ in 0..4 {
+        wall.extend(Hai::all_type(PlayerNumber::Four));
+    }
+    let mut table = Table::deal(&wall, PlayerNumber::Four).unwrap();
+    let live_wall_remaining = table.live_wall_remaining();
+    assert_eq!(table.wanpai_remaining(), DEFAULT_WANPAI_SIZE);
+    assert_eq!(table.rinshanhai_remaining(), MAX_RINSHANHAI_NUMBER);
+    assert_eq!(table.dora_indicators(), vec![Hai::Jihai(3)]);
+    assert!(table.kan_dora_indicators().is_empty());
+
+    let hai = table.draw_rinshan(1).unwrap();
+    assert_eq!(hai, *wall.last().unwrap());
+    assert_eq!(table.tehai(1).unwrap().juntehai.len(), 14);
+    assert_eq!(table.wanpai_remaining(), DEFAULT_WANPAI_SIZE);
+    assert_eq!(table.live_wall_remaining(), live_wall_remaining - 1);
+    assert_eq!(table.haiyama().remaining(), 136 - 53);
+    assert_eq!(table.dora_indicators(), vec![Hai::Jihai(3), Hai::Jihai(1)]);
+    assert_eq!(table.kan_dora_indicators(), vec![Hai::Jihai(1)]);
+    assert!(table.draw_rinshan(4).is_err());
+
+    for (seat, rinshanhai) in [(2, Hai::Jihai(6)), (3, Hai::Jihai(5)), (0, Hai::Jihai(4))] {
+        assert_eq!(table.draw_rinshan(seat), Ok(rinshanhai));
+    }
+    assert_eq!(table.live_wall_remaining(), live_wall_remaining - 4);
+    assert_eq!(table.rinshanhai_remaining(), 0);
+    assert_eq!(
+        table.dora_indicators(),
+        vec![
+            Hai::Jihai(3),
+            Hai::Jihai(1),
+            Hai::Souzu(8),
+            Hai::Souzu(6),
+            Hai::Souzu(4)
+        ]
+    );
+    assert!(table.draw_rinshan(0).is_err());
+
+    let mut table = Table::deal_with_wanpai(&wall, PlayerNumber::Four, 0).unwrap();
+    assert!(table.draw_rinshan(0).is_err());
+    assert!(table.dora_indicators().is_empty());
+    assert!(table.kan_dora_indicators().is_empty());
+
+    let mut table = Table::deal(&wall[..66], PlayerNumber::Four).unwrap();
+    assert_eq!(table.live_wall_remaining(), 0);
+    assert!(table.draw_rinshan(0).is_err());
+}
+
+#[test]
+fn test_riichi_ankan_rule() {
+    let mut game_manager = GameManager::new(PlayerNumber::Four);
+    assert!(game_manager.riichi_ankan());
+    game_manager.set_riichi_ankan(false);
+    game_manager.command("init 1112m456p789s1113z").unwrap();
+    game_manager.command("riichi 3z").unwrap();
+    game_manager.command("draw 1z").unwrap();
+    assert!(game_manager.command("ankan 1z 5z").is_err());
+    game_manager.command("discard 1z").unwrap();
+
+    game_manager.new_round();
+    assert!(!game_manager.riichi_ankan());
+    game_manager.set_riichi_ankan(true);
+    game_manager.command("init 1112m456p789s1113z").unwrap();
+    game_manager.command("riichi 3z").unwrap();
+    game_manager.command("draw 1z").unwrap();
+    game_manager.command("ankan 1z 5z").unwrap();
+}
+
+#[test]
+fn test_ryuukyoku_payments() {
+    assert_eq!(
+        score::ryuukyoku_payments(&[true, false, false, false]),
+        [3000, -1000, -1000, -1000]
+    );
+    assert_eq!(
+        score::ryuukyoku_payments(&[false, true, false, true]),
+        [-1500, 1500, -1500, 1500]
+    );
+    assert_eq!(
+        score::ryuukyoku_payments(&[true, true, false, true]),
+        [1000, 1000, -3000, 1000]
+    );
+    assert_eq!(score::ryuukyoku_payments(&[true; 4]), [0; 4]);
+    assert_eq!(score::ryuukyoku_payments(&[false; 4]), [0; 4]);
+}
+
+#[test]
+fn test_draw_count() {
+    let mut game_manager = GameManager::new(PlayerNumber::Four);
+    game_manager.command("init 123m456p789s1113z").unwrap();
+    assert_eq!(game_manager.draw_count(), 0);
+    for hai in ["4z", "5z", "6z"].iter() {
+        game_manager.command(&format!("draw {}", hai)).unwrap();
+        game_manager.command(&format!("discard {}", hai)).unwrap();
+    }
+    game_manager.command("seen 7z").unwrap();
+    assert_eq!(game_manager.draw_count(), 3);
+    // Back to before drawing 6z.
+    for _ in 0..3 {
+        game_manager.back(true).unwrap();
+    }
+    assert_eq!(game_manager.draw_count(), 2);
+}
+
+#[test]
+fn test_tehai_from_majsoul() {
+    let codes = [11, 12, 13, 24, 52, 26, 37, 38, 39, 41, 41, 45, 46];
+    let tehai = Tehai::from_majsoul(&codes, PlayerNumber::Four).unwrap();
+    assert_eq!(
+        tehai,
+        Tehai::new("123m456p789s1156z", PlayerNumber::Four).unwrap()
+    );
+    assert!(Tehai::from_majsoul(&[10, 11], PlayerNumber::Four).is_err());
+    assert!(Tehai::from_majsoul(&[48], PlayerNumber::Four).is_err());
+    assert!(Tehai::from_majsoul(&[51], PlayerNumber::Three).is_err());
+}
+
+#[test]
+fn test_discard_profile() {
+    let mut game_manager = GameManager::new(PlayerNumber::Four);
+    game_manager.command("init 1199m123p456s135z").unwrap();
+    for (draw, discard) in [("2p", "1z"), ("3s", "1m"), ("4p", "1m"), ("7s", "9m")].iter() {
+        game_manager.command(&format!("draw {}", draw)).unwrap();
+        game_manager
+            .command(&format!("discard {}", discard))
+            .unwrap();
+    }
+    game_manager.command("draw 8m").unwrap();
+    game_manager
+        .operate(Operation::Tehai(TehaiOperation::Discard {
+            hai: Hai::Manzu(8),
+            tsumogiri: true,
+        }))
+        .unwrap();
+
+    let profile = game_manager.discard_profile();
+    assert_eq!(
+        profile,
+        DiscardProfile {
+            suit: [4, 0, 0, 1],
+            early_jihai: 1,
+            tsumogiri: 1,
+            riichi: None,
+        }
+    );
+    assert_eq!(profile.dominant_suit(), Some(Suit::Manzu));
+    assert_eq!(
+        GameManager::new(PlayerNumber::Four)
+            .discard_profile()
+            .dominant_suit(),
+        None
+    );
+}
+
+#[test]
+fn test_shanten_breakdown() {
+    let tehai = Tehai::new("1155m2299p3388s12z", PlayerNumber::Four).unwrap();
+    let breakdown = tehai.shanten_breakdown(PlayerNumber::Four).unwrap();
+    assert_eq!(breakdown.chiitoitsu, Some(0));
+    assert!(breakdown.mentsute > 0);
+    assert!(breakdown.kokushimusou.unwrap() > 0);
+    assert_eq!(
+        breakdown.min,
+        breakdown
+            .mentsute
+            .min(breakdown.chiitoitsu.unwrap())
+            .min(breakdown.kokushimusou.unwrap())
+    );
+    assert_eq!(
+        breakdown.min,
+        tehai.analyze(PlayerNumber::Four, None).unwrap().0
+    );
+
+    let tehai = Tehai::new("123m456p78s11z[222z]1z", PlayerNumber::Four).unwrap();
+    let breakdown = tehai.shanten_breakdown(PlayerNumber::Four).unwrap();
+    assert_eq!(breakdown.chiitoitsu, None);
+    assert_eq!(breakdown.kokushimusou, None);
+    assert_eq!(breakdown.min, breakdown.mentsute);
+}
+
+#[test]
+fn test_call_analysis() {
+    let tehai = Tehai::new("123m456p78s1155z9m", PlayerNumber::Four).unwrap();
+    let mut haiyama = Haiyama::new(PlayerNumber::Four);
+    haiyama.discard_with_vec(&tehai.juntehai, true).unwrap();
+    haiyama.discard(&Hai::Jihai(5)).unwrap();
+    let analysis = tehai
+        .call_analysis(
+            &Naku::Pon(Mentsu::Koutsu(Hai::Jihai(5))),
+            PlayerNumber::Four,
+            &haiyama,
+        )
+        .unwrap();
+    assert_eq!(analysis.shanten, 1);
+    assert_eq!(analysis.naku_shanten, 0);
+    assert_eq!(analysis.sutehai, Some(Hai::Manzu(9)));
+    assert_eq!(analysis.naku_ukeire, 8);
+    assert!(analysis.advances());
+
+    // Pon of the only toitsu loses jantou.
+    let tehai = Tehai::new("123m456p1357s11z9m", PlayerNumber::Four).unwrap();
+    let analysis = tehai
+        .call_analysis(
+            &Naku::Pon(Mentsu::Koutsu(Hai::Jihai(1))),
+            PlayerNumber::Four,
+            &haiyama,
+        )
+        .unwrap();
+    assert_eq!(analysis.shanten, 1);
+    assert_eq!(analysis.naku_shanten, 1);
+    assert!(!analysis.advances());
+    assert!(tehai
+        .call_analysis(
+            &Naku::Pon(Mentsu::Koutsu(Hai::Jihai(7))),
+            PlayerNumber::Four,
+            &haiyama
+        )
+        .is_err());
+}
+
+#[test]
+fn test_history_of() {
+    let mut game_manager = GameManager::new(PlayerNumber::Four);
+    game_manager.command("init 123m456p789s1123z").unwrap();
+    game_manager.command("draw 4z").unwrap();
+    game_manager.command("discard 4z").unwrap();
+    game_manager.command("seen 5z").unwrap();
+    game_manager.command("pon 1z").unwrap();
+    game_manager.command("discard 3z").unwrap();
+
+    let discards = game_manager.history_of(&[OperationKind::TehaiDiscard]);
+    assert_eq!(discards.len(), 2);
+    let sutehai: Vec<Hai> = discards
+        .iter()
+        .map(|(op, _, _)| match op {
+            Operation::Tehai(TehaiOperation::Discard { hai, .. }) => *hai,
+            _ => unreachable!(),
+        })
+        .collect();
+    assert_eq!(sutehai, vec![Hai::Jihai(4), Hai::Jihai(3)]);
+    assert_eq!(
+        game_manager
+            .history_of(&[OperationKind::Chii, OperationKind::Pon])
+            .len(),
+        1
+    );
+    assert!(game_manager.history_of(&[]).is_empty());
+
+    let mut game_manager = GameManager::new(PlayerNumber::Four);
+    game_manager.command("init 123m456p789s1134z").unwrap();
+    game_manager.command("draw 5z").unwrap();
+    game_manager.command("discard 5z").unwrap();
+    game_manager.command("draw 1z").unwrap();
+    game_manager.command("riichi 4z").unwrap();
+    game_manager.command("draw 6z").unwrap();
+    game_manager.command("discard 6z").unwrap();
+
+    let discards: Vec<(Hai, bool)> = game_manager
+        .history_of(&[OperationKind::TehaiDiscard])
+        .iter()
+        .map(|(op, _, _)| match op {
+            Operation::Tehai(TehaiOperation::Discard { hai, .. }) => (*hai, false),
+            Operation::Tehai(TehaiOperation::Riichi { hai, .. }) => (*hai, true),
+            _ => unreachable!(),
+        })
+        .collect();
+    assert_eq!(
+        discards,
+        vec![
+            (Hai::Jihai(5), false),
+            (Hai::Jihai(4), true),
+            (Hai::Jihai(6), false)
+        ]
+    );
+    assert_eq!(game_manager.history_of(&[OperationKind::Riichi]).len(), 1);
+
+    let profile = game_manager.discard_profile();
+    assert_eq!(profile.suit, [0, 0, 0, 3]);
+    assert_eq!(profile.riichi, Some(1));
+}
+
+#[test]
+fn test_distance_to() {
+    let tehai = Tehai::new("123m456p789s1123z", PlayerNumber::Four).unwrap();
+    let target = Tehai::new("123m456p789s1145z", PlayerNumber::Four).unwrap();
+    assert_eq!(tehai.distance_to(&target), 2);
+    assert_eq!(target.distance_to(&tehai), 2);
+    assert_eq!(tehai.distance_to(&tehai), 0);
+
+    let longer = Tehai::new("123m456p789s11234z", PlayerNumber::Four).unwrap();
+    assert_eq!(tehai.distance_to(&longer), 1);
+}
+
+#[test]
+fn test_paifu_parse_round() {
+    let log = "# East 1\n\
+               init 123m456p789s1123z\n\
+               draw 4z\n\
+               discard 4z # tsumogiri\n\
+               \n\
+               seen 56z\n\
+               pon 1z\n\
+               discard 3z\n";
+    let operations = paifu::parse_round(log, PlayerNumber::Four).unwrap();
+    assert_eq!(operations.len(), 6);
+
+    let mut game_manager = GameManager::new(PlayerNumber::Four);
+    for op in operations.iter().cloned() {
+        game_manager.operate(op).unwrap();
+    }
+    assert_eq!(game_manager.state, State::LackOneHai);
+    assert_eq!(game_manager.sutehai(), &vec![Hai::Jihai(4), Hai::Jihai(3)]);
+    assert_eq!(game_manager.tehai().unwrap().fuuro.len(), 1);
+    assert_eq!(
+        GameManager::verify_replay(&game_manager, &operations),
+        Ok(())
+    );
+
+    let error = paifu::parse_round("init 123m\nfoo 1z", PlayerNumber::Four).unwrap_err();
+    assert!(error.starts_with("Line 2:"));
+}
+
+#[test]
+fn test_shanten_without_haiyama() {
+    let tehai = Tehai::new("1235m456p78s1123z", PlayerNumber::Four).unwrap();
+    let full = Haiyama::new(PlayerNumber::Four);
+    let mut counts = [4; 34];
+    for hai in [Hai::Souzu(6), Hai::Souzu(9), Hai::Manzu(4)].iter() {
+        counts[hai.to_index() as usize] = 0;
+    }
+    let drained = Haiyama::from_counts(&counts, PlayerNumber::Four).unwrap();
+
+    let (shanten, ukeire) = tehai.shanten_and_ukeire(PlayerNumber::Four, &full).unwrap();
+    let (drained_shanten, drained_ukeire) = tehai
+        .shanten_and_ukeire(PlayerNumber::Four, &drained)
+        .unwrap();
+    assert_eq!(shanten, drained_shanten);
+    assert_eq!(tehai.shanten(PlayerNumber::Four).unwrap(), shanten);
+    assert_eq!(
+        ukeire.keys().collect::<Vec<_>>(),
+        drained_ukeire.keys().collect::<Vec<_>>()
+    );
+    assert_ne!(ukeire, drained_ukeire);
+
+    let mut tehai = tehai;
+    tehai.juntehai.push(Hai::Jihai(7));
+    tehai.juntehai.sort();
+    assert_eq!(
+        tehai.shanten(PlayerNumber::Four).unwrap(),
+        tehai.analyze(PlayerNumber::Four, None).unwrap().0
+    );
+}
+
+#[test]
+fn test_chiitoitsu_machi() {
+    let tehai = Tehai::new("11223344m5566p7z", PlayerNumber::Four).unwrap();
+    let machi: Vec<Hai> = tehai
+        .machi(PlayerNumber::Four)
+        .unwrap()
+        .into_iter()
+        .collect();
+    assert_eq!(machi, vec![Hai::Jihai(7)]);
+    assert_eq!(tehai.shanten(PlayerNumber::Four).unwrap(), 0);
+
+    let mut completed = tehai.clone();
+    completed.juntehai.push(Hai::Jihai(7));
+    completed.juntehai.sort();
+    let breakdown = completed.shanten_breakdown(PlayerNumber::Four).unwrap();
+    assert_eq!(breakdown.chiitoitsu, Some(-1));
+    assert!(breakdown.mentsute >= 0);
+    assert_eq!(breakdown.min, -1);
+}
+
+#[test]
+fn test_compare_taatsu() {
+    let tehai = Tehai::new("123m456p34s79s1z123z", PlayerNumber::Four).unwrap();
+    let mut haiyama = Haiyama::new(PlayerNumber::Four);
+    haiyama.discard_with_vec(&tehai.juntehai, true).unwrap();
+    let ryanmen = (Hai::Souzu(3), Hai::Souzu(4));
+    let kanchan = (Hai::Souzu(9), Hai::Souzu(7));
+    assert_eq!(
+        tehai
+            .compare_taatsu(ryanmen, kanchan, PlayerNumber::Four, &haiyama)
+            .unwrap(),
+        std::cmp::Ordering::Greater
+    );
+    assert_eq!(
+        tehai
+            .compare_taatsu(kanchan, ryanmen, PlayerNumber::Four, &haiyama)
+            .unwrap(),
+        std::cmp::Ordering::Less
+    );
+
+    // Kanchan with all 4 of 8s left beats ryanmen with only 2s and 5s left once each.
+    haiyama
+        .discard_with_vec(&vec![Hai::Souzu(2), Hai::Souzu(2), Hai::Souzu(2)], true)
+        .unwrap();
+    haiyama
+        .discard_with_vec(&vec![Hai::Souzu(5), Hai::Souzu(5), Hai::Souzu(5)], true)
+        .unwrap();
+    assert_eq!(
+        tehai
+            .compare_taatsu(ryanmen, kanchan, PlayerNumber::Four, &haiyama)
+            .unwrap(),
+        std::cmp::Ordering::Less
+    );
+    assert!(tehai
+        .compare_taatsu(
+            (Hai::Souzu(3), Hai::Souzu(9)),
+            kanchan,
+            PlayerNumber::Four,
+            &haiyama
+        )
+        .is_err());
+    assert!(tehai
+        .compare_taatsu(
+            (Hai::Souzu(5), Hai::Souzu(6)),
+            kanchan,
+            PlayerNumber::Four,
+            &haiyama
+        )
+        .is_err());
+}
+
+#[test]
+fn test_back_kan_without_rinshanhai() {
+    // Daiminkan from LackOneHai, ankan and kakan from FullHai.
+    let cases = [
+        (
+            vec!["init 1112m456p789s111s"],
+            "daiminkan 1s",
+            State::LackOneHai,
+        ),
+        (
+            vec!["init 1111m456p789s1z11s2s"],
+            "ankan 1m",
+            State::FullHai,
+        ),
+        (
+            vec!["init 1235m456p789s11s1z", "pon 1s", "discard 1z", "draw 1s"],
+            "kakan 1s",
+            State::FullHai,
+        ),
+    ];
+    for (commands, kan, state) in cases.iter() {
+        let mut game_manager = GameManager::new(PlayerNumber::Four);
+        for command in commands {
+            game_manager.command(command).unwrap();
+        }
+        let tehai = game_manager.tehai().unwrap().clone();
+        let haiyama = game_manager.haiyama().clone();
+        assert_eq!(game_manager.state, *state);
+
+        game_manager.command(kan).unwrap();
+        assert_eq!(game_manager.state, State::WaitForRinshanhai);
+        game_manager.back(true).unwrap();
+        assert_eq!(game_manager.state, *state);
+        assert_eq!(game_manager.tehai().unwrap(), &tehai);
+        assert_eq!(game_manager.haiyama().to_json(), haiyama.to_json());
+    }
+}
+
+#[test]
+fn test_haiyama_can_draw() {
+    let mut haiyama = Haiyama::new(PlayerNumber::Three);
+    assert!(haiyama.can_draw(&Hai::Pinzu(3)));
+    assert!(!haiyama.can_draw(&Hai::Manzu(5)));
+    for _ in 0..3 {
+        haiyama.discard(&Hai::Pinzu(3)).unwrap();
+        assert!(haiyama.can_draw(&Hai::Pinzu(3)));
+    }
+    haiyama.discard(&Hai::Pinzu(3)).unwrap();
+    assert!(!haiyama.can_draw(&Hai::Pinzu(3)));
+}
+
+#[test]
+fn test_naku_options() {
+    let tehai = Tehai::new("23445m456p789s11z", PlayerNumber::Four).unwrap();
+    let mut haiyama = Haiyama::new(PlayerNumber::Four);
+    haiyama.discard_with_vec(&tehai.juntehai, true).unwrap();
+    haiyama.discard(&Hai::Manzu(4)).unwrap();
+    let options = tehai
+        .naku_options(&Hai::Manzu(4), PlayerNumber::Four, &haiyama)
+        .unwrap();
+    let naku_vec: Vec<String> = options
+        .iter()
+        .map(|(naku, _)| match naku {
+            Naku::Chii { juntsu, .. } => format!("chii {}", juntsu),
+            Naku::Pon(koutsu) => format!("pon {}", koutsu),
+            Naku::Kan(_) => "kan".to_string(),
+        })
+        .collect();
+    assert_eq!(
+        naku_vec,
+        vec!["chii [2m3m4m]", "chii [3m4m5m]", "pon [4m4m4m]"]
+    );
+    for (naku, analysis) in &options {
+        assert_eq!(
+            analysis,
+            &tehai
+                .call_analysis(naku, PlayerNumber::Four, &haiyama)
+                .unwrap()
+        );
+    }
+
+    let options = tehai
+        .naku_options(&Hai::Jihai(1), PlayerNumber::Four, &haiyama)
+        .unwrap();
+    assert_eq!(options.len(), 1);
+    let tehai = Tehai::new("111m456p789s1157z", PlayerNumber::Four).unwrap();
+    let options = tehai
+        .naku_options(&Hai::Manzu(1), PlayerNumber::Four, &haiyama)
+        .unwrap();
+    assert_eq!(options.len(), 2);
+    assert_eq!(options[1].1.sutehai, None);
+    assert_eq!(options[1].1.naku_shanten, 1);
+}
+
+#[test]
+fn test_json_version() {
+    let mut game_manager = GameManager::new(PlayerNumber::Four);
+    game_manager.command("init 123m456p789s1113z").unwrap();
+    let mut json = game_manager.to_json();
+    assert_eq!(json["version"], JSON_VERSION);
+    assert!(GameManager::check_json_version(&json).is_ok());
+
+    json["version"] = serde_json::json!(JSON_VERSION + 1);
+    assert_eq!(
+        GameManager::check_json_version(&json),
+        Err(format!(
+            "Unsupported version {}, the latest supported version is {}.",
+            JSON_VERSION + 1,
+            JSON_VERSION
+        ))
+    );
+
+    json.as_object_mut().unwrap().remove("version");
+    assert!(GameManager::check_json_version(&json).is_ok());
+}
+
+#[test]
+fn test_is_safe() {
+    let visible = [0; 34];
+    let sutehai = Hai::from_string_unordered("1m4p1z", PlayerNumber::Four).unwrap();
+    assert_eq!(
+        defense::is_safe(&Hai::Pinzu(4), &sutehai, &visible),
+        (Safety::Safe, vec![SafetyReason::Genbutsu])
+    );
+    assert_eq!(
+        defense::is_safe(&Hai::Pinzu(1), &sutehai, &visible),
+        (Safety::Likely, vec![SafetyReason::Suji])
+    );
+    assert_eq!(
+        defense::is_safe(&Hai::Pinzu(6), &sutehai, &visible),
+        (Safety::Dangerous, vec![])
+    );
+    assert_eq!(
+        defense::is_safe(&Hai::Jihai(2), &sutehai, &visible),
+        (Safety::Dangerous, vec![])
+    );
+}
+
+#[test]
+fn test_tile_count() {
+    let mut tehai = Tehai::new("2345m456p789s11z", PlayerNumber::Four).unwrap();
+    tehai
+        .fuuro
+        .push(Mentsu::Juntsu(Hai::Manzu(3), Hai::Manzu(4), Hai::Manzu(5)));
+    assert_eq!(tehai.tile_count(&Hai::Manzu(4)), 2);
+    assert_eq!(tehai.tile_count(&Hai::Manzu(2)), 1);
+    assert_eq!(tehai.tile_count(&Hai::Jihai(1)), 2);
+    assert_eq!(tehai.tile_count(&Hai::Jihai(2)), 0);
+
+    tehai.fuuro.push(Mentsu::Kantsu(Hai::Jihai(2)));
+    assert_eq!(tehai.tile_count(&Hai::Jihai(2)), 4);
+}
+
+#[test]
+fn test_tenpai_sutehai() {
+    let mut game_manager = GameManager::new(PlayerNumber::Four);
+    for line in [
+        "init 123m456p789s111z2s",
+        "draw 7s",
+        "discard 7s",
+        "draw 7s",
+        "seen 22s",
+    ] {
+        game_manager.command(line).unwrap();
+    }
+    let (_, conditions) = game_manager.tehai_analyze().unwrap();
+    assert_eq!(conditions[0].sutehai, Hai::Souzu(2));
+    assert!(conditions[0].furiten);
+
+    let conditions = game_manager.tenpai_sutehai().unwrap();
+    assert_eq!(conditions.len(), 2);
+    assert_eq!(conditions[0].sutehai, Hai::Souzu(7));
+    assert!(!conditions[0].furiten);
+    assert_eq!(conditions[1].sutehai, Hai::Souzu(2));
+    assert!(conditions[1].furiten);
+
+    let mut game_manager = GameManager::new(PlayerNumber::Four);
+    game_manager.command("init 123m456p789s1357z").unwrap();
+    assert!(game_manager.tenpai_sutehai().is_err());
+}
+
+#[test]
+fn test_mark_seen_many() {
+    let mut haiyama = Haiyama::new(PlayerNumber::Four);
+    let tiles = Hai::from_string_unordered("13m5z", PlayerNumber::Four).unwrap();
+    haiyama.mark_seen_many(&tiles).unwrap();
+    assert_eq!(haiyama[&Hai::Manzu(1)], 3);
+    assert_eq!(haiyama[&Hai::Jihai(5)], 3);
+
+    let backup = haiyama.to_json();
+    let tiles = Hai::from_string_unordered("2m5555z", PlayerNumber::Four).unwrap();
+    assert!(haiyama.mark_seen_many(&tiles).is_err());
+    assert_eq!(haiyama.to_json(), backup);
+    assert_eq!(haiyama[&Hai::Manzu(2)], 4);
+}
+
+#[test]
+fn test_pair_candidates() {
+    let tehai = Tehai::new("123m456p789s11223z", PlayerNumber::Four).unwrap();
+    assert_eq!(
+        tehai.pair_candidates(PlayerNumber::Four),
+        Ok(vec![Hai::Jihai(1), Hai::Jihai(2)])
+    );
+
+    let tehai = Tehai::new("123m456p789s1123z", PlayerNumber::Four).unwrap();
+    assert!(tehai.pair_candidates(PlayerNumber::Four).is_err());
+}
+
+#[test]
+fn test_preview() {
+    let mut game_manager = GameManager::new(PlayerNumber::Four);
+    game_manager.command("init 123m456p789s1113z").unwrap();
+    let op = Operation::Tehai(TehaiOperation::Add {
+        hai: Hai::Jihai(3),
+        haiyama_sensitive: true,
+    });
+    let preview = game_manager.preview(&op).unwrap();
+    assert_eq!(preview.state, State::FullHai);
+    assert_eq!(preview.tehai().unwrap().tile_total(), 14);
+    assert_eq!(preview.haiyama()[&Hai::Jihai(3)], 2);
+    assert_eq!(game_manager.state, State::LackOneHai);
+    assert_eq!(game_manager.tehai().unwrap().tile_total(), 13);
+    assert_eq!(game_manager.haiyama()[&Hai::Jihai(3)], 3);
+    assert_eq!(preview.history().len(), 2);
+    assert_eq!(game_manager.history().len(), 1);
+}
+
+#[test]
+fn test_machi_after_pon() {
+    let mut game_manager = GameManager::new(PlayerNumber::Four);
+    game_manager.command("init 13m456p789s11223z").unwrap();
+    game_manager.command("pon 1z").unwrap();
+
+    let (shanten, conditions) = game_manager.tehai_analyze().unwrap();
+    assert_eq!(shanten, 0);
+    assert_eq!(conditions[0].sutehai, Hai::Jihai(3));
+    assert_eq!(
+        conditions[0].machihai.iter().collect::<Vec<_>>(),
+        vec![(&Hai::Manzu(2), &4)]
+    );
+
+    game_manager.command("discard 3z").unwrap();
+    let tehai = game_manager.tehai().unwrap();
+    assert_eq!(tehai.juntehai.len(), 10);
+    assert_eq!(
+        tehai
+            .machi(PlayerNumber::Four)
+            .unwrap()
+            .into_iter()
+            .collect::<Vec<_>>(),
+        vec![Hai::Manzu(2)]
+    );
+    let (shanten, ukeire) = tehai
+        .shanten_and_ukeire(PlayerNumber::Four, game_manager.haiyama())
+        .unwrap();
+    assert_eq!(shanten, 0);
+    assert_eq!(
+        ukeire.into_iter().collect::<Vec<_>>(),
+        vec![(Hai::Manzu(2), 4)]
+    );
+}
+
+#[test]
+fn test_improving_tiles() {
+    let tehai = Tehai::new("123m456p789s35s11z", PlayerNumber::Four).unwrap();
+    let haiyama = Haiyama::new(PlayerNumber::Four);
+    let improving_tiles = tehai.improving_tiles(PlayerNumber::Four, &haiyama).unwrap();
+    let kind_of = |hai| {
+        improving_tiles
+            .iter()
+            .find(|(item, _)| *item == hai)
+            .map(|(_, kind)| *kind)
+    };
+    assert_eq!(
+        kind_of(Hai::Souzu(4)),
+        Some(ImprovementKind::ShantenAdvance)
+    );
+    assert_eq!(kind_of(Hai::Souzu(2)), Some(ImprovementKind::ShapeImprove));
+    assert_eq!(kind_of(Hai::Souzu(6)), Some(ImprovementKind::ShapeImprove));
+    assert_eq!(kind_of(Hai::Souzu(7)), Some(ImprovementKind::Useless));
+    assert_eq!(kind_of(Hai::Manzu(9)), Some(ImprovementKind::Useless));
+    assert_eq!(improving_tiles.len(), 34);
+}
+
+#[test]
+fn test_resume() {
+    let tehai = Tehai::new("123m456p789s11123z", PlayerNumber::Four).unwrap();
+    let mut haiyama = Haiyama::new(PlayerNumber::Four);
+    let sutehai = Hai::from_string_unordered("9m", PlayerNumber::Four).unwrap();
+    haiyama.discard_with_vec(&tehai.juntehai, true).unwrap();
+    haiyama.discard_with_vec(&sutehai, true).unwrap();
+
+    let mut game_manager = GameManager::resume(
+        PlayerNumber::Four,
+        tehai.clone(),
+        haiyama.clone(),
+        State::FullHai,
+        sutehai.clone(),
+    )
+    .unwrap();
+    game_manager.command("discard 3z").unwrap();
+    assert_eq!(game_manager.state, State::LackOneHai);
+    assert_eq!(game_manager.sutehai(), &vec![Hai::Manzu(9), Hai::Jihai(3)]);
+    assert!(game_manager
+        .tehai()
+        .unwrap()
+        .machi(PlayerNumber::Four)
+        .unwrap()
+        .contains(&Hai::Jihai(2)));
+
+    assert_eq!(
+        GameManager::resume(
+            PlayerNumber::Four,
+            tehai.clone(),
+            haiyama.clone(),
+            State::LackOneHai,
+            sutehai.clone(),
+        )
+        .unwrap_err(),
+        "The number of hai on hand does not match LackOneHai."
+    );
+    assert!(GameManager::resume(
+        PlayerNumber::Four,
+        tehai.clone(),
+        haiyama.clone(),
+        State::WaitToInit,
+        sutehai.clone()
+    )
+    .is_err());
+
+    assert_eq!(
+        GameManager::resume(
+            PlayerNumber::Four,
+            tehai.clone(),
+            Haiyama::new(PlayerNumber::Four),
+            State::FullHai,
+            sutehai.clone(),
+        )
+        .unwrap_err(),
+        "5 of 1m found in tehai, sutehai and haiyama."
+    );
+    let mut tehai = Tehai::new("123m456p789s1z[2222p]", PlayerNumber::Four).unwrap();
+    tehai.juntehai.push(Hai::Pinzu(4));
+    let mut haiyama = Haiyama::new(PlayerNumber::Four);
+    haiyama.discard_with_vec(&tehai.juntehai, true).unwrap();
+    haiyama
+        .discard_with_vec(&vec![Hai::Pinzu(2); 4], true)
+        .unwrap();
+    assert!(GameManager::resume(
+        PlayerNumber::Four,
+        tehai.clone(),
+        haiyama.clone(),
+        State::FullHai,
+        vec![]
+    )
+    .is_ok());
+    assert_eq!(
+        GameManager::resume(
+            PlayerNumber::Four,
+            tehai,
+            haiyama,
+            State::FullHai,
+            vec![Hai::Pinzu(2)]
+        )
+        .unwrap_err(),
+        "5 of 2p found in tehai, sutehai and haiyama."
+    );
+}
+
+#[test]
+fn test_ron_payments() {
+    let winners = [(1, 8000), (3, 2000)];
+    assert_eq!(
+        score::ron_payments(2, &winners, false, 0, 0),
+        Ok([0, 8000, -10000, 2000])
+    );
+    assert_eq!(
+        score::ron_payments(2, &winners, true, 0, 0),
+        Ok([0, 0, -2000, 2000])
+    );
+    assert_eq!(
+        score::ron_payments(0, &winners, true, 0, 0),
+        Ok([-8000, 8000, 0, 0])
+    );
+    assert_eq!(
+        score::ron_payments(2, &winners, false, 2, 1),
+        Ok([0, 8000, -10600, 3600])
+    );
+    assert_eq!(
+        score::ron_payments(2, &winners, true, 2, 1),
+        Ok([0, 0, -2600, 3600])
+    );
+    assert!(score::ron_payments(1, &winners, false, 0, 0).is_err());
+    assert!(score::ron_payments(0, &[(1, 1000), (1, 1000)], false, 0, 0).is_err());
+    assert!(score::ron_payments(0, &[], false, 0, 0).is_err());
+}
+
+#[test]
+fn test_is_dead_shape() {
+    let tehai = Tehai::new("123m456p789s1112z", PlayerNumber::Four).unwrap();
+    let mut haiyama = Haiyama::new(PlayerNumber::Four);
+    haiyama.discard_with_vec(&tehai.juntehai, true).unwrap();
+    assert_eq!(tehai.is_dead_shape(PlayerNumber::Four, &haiyama), Ok(false));
+
+    let tiles = Hai::from_string_unordered("222z", PlayerNumber::Four).unwrap();
+    haiyama.mark_seen_many(&tiles).unwrap();
+    assert_eq!(tehai.is_dead_shape(PlayerNumber::Four, &haiyama), Ok(true));
+}